@@ -0,0 +1,68 @@
+//! Measures the cost of analysing a single frame at different points in a long track.
+//! The cost should be the same at the end of the track as it is at the start.
+//!
+//! Fails if the cost at the end is more than MAX_RATIO times the cost at the start.
+//!
+//! Run with `cargo bench`.
+
+#[path = "../src/audio/mod.rs"]
+#[allow(dead_code, unused_imports)]
+mod audio;
+
+use std::time::Instant;
+use audio::{AudioSource, SampleStore};
+
+const SAMPLE_RATE: u32 = 44100;
+const CHANNELS: u16 = 2;
+const TRACK_MINUTES: usize = 60;
+const FRAMES_PER_POSITION: usize = 20;
+/// Time between frames when sampling a position, in seconds.
+const FRAME_TIME: f32 = 1.0 / 60.0;
+/// The largest allowed cost at the end of the track, relative to the start.
+/// This allows for timing noise, but not for a lookup that grows with the position.
+const MAX_RATIO: f64 = 3.0;
+
+fn main() {
+    println!("generating {} minute track...", TRACK_MINUTES);
+    let frame_count = (SAMPLE_RATE as usize) * 60 * TRACK_MINUTES;
+    let samples = (0..frame_count)
+        .flat_map(|n| {
+            let t = (n as f32) / (SAMPLE_RATE as f32);
+            // A tone with a click every half second, with the click only on the left.
+            let click = if t.fract() % 0.5 < 0.01 { 0.5 } else { 0.0 };
+            let tone = 0.25 * (t * 220.0 * std::f32::consts::TAU).sin();
+            [tone + click, tone]
+        });
+
+    let start = Instant::now();
+    let store = SampleStore::new(samples, CHANNELS, SAMPLE_RATE);
+    println!("stored {} samples in {:.2?}", frame_count * (CHANNELS as usize), start.elapsed());
+
+    let start = Instant::now();
+    let mut source = AudioSource::from_store(store);
+    println!("analysed track in {:.2?}", start.elapsed());
+
+    println!("{:>8} {:>14}", "minute", "time per frame");
+    let mut results = Vec::new();
+    for minute in (0..TRACK_MINUTES).step_by(5).chain([TRACK_MINUTES - 1]) {
+        let position = (minute as f32) * 60.0;
+        // The fastest frame is used, so that other work on the machine has less effect.
+        let per_frame = (0..FRAMES_PER_POSITION)
+            .map(|frame| {
+                let start = Instant::now();
+                // Analyse each channel separately, which is the most expensive case.
+                std::hint::black_box(source.get_frame_data(position + (frame as f32) * FRAME_TIME, true));
+                start.elapsed()
+            })
+            .min()
+            .unwrap();
+        println!("{:>8} {:>14.2?}", minute, per_frame);
+        results.push(per_frame);
+    }
+
+    let first = results.first().unwrap().as_secs_f64();
+    let last = results.last().unwrap().as_secs_f64();
+    let ratio = last / first;
+    println!("cost at end relative to start: {:.2}x", ratio);
+    assert!(ratio < MAX_RATIO, "frame cost grows with position: {:.2}x at the end of the track", ratio);
+}
@@ -7,8 +7,10 @@ use winit::{
 };
 
 use crate::{
-    audio::AudioSource,
-    renderer::{Renderer, Display, Surface}
+    audio::{AudioSource, PlaybackClock},
+    renderer::{Renderer, Display, Surface},
+    script,
+    watcher::FileWatcher
 };
 
 /// State of the active window.
@@ -24,18 +26,42 @@ pub struct App {
     display: Display,
     window: Option<WindowState>,
 
-    start_time: chrono::DateTime<chrono::Utc>,
+    clock: PlaybackClock,
+
+    script_path: String,
+    script_watcher: Option<FileWatcher>,
 }
 
 impl App {
-    pub fn new(renderer: Renderer, audio_source: AudioSource, display: Display) -> Self {
+    pub fn new(renderer: Renderer, audio_source: AudioSource, display: Display, clock: PlaybackClock, script_path: String) -> Self {
+        let script_watcher = FileWatcher::new(script_path.as_ref())
+            .inspect_err(|e| eprintln!("could not watch script for changes: {}", e))
+            .ok();
         Self {
             renderer,
             audio_source,
             display,
             window: None,
 
-            start_time: chrono::Utc::now()
+            clock,
+
+            script_path,
+            script_watcher
+        }
+    }
+
+    /// Rebuild the display if the script has changed.
+    /// If the new script has errors, the old display is kept.
+    fn reload_script(&mut self) {
+        if !self.script_watcher.as_ref().is_some_and(FileWatcher::changed) {
+            return;
+        }
+        match script::parse_file(&self.script_path, &self.renderer) {
+            Ok(display) => {
+                self.display = display;
+                eprintln!("reloaded {}", self.script_path);
+            },
+            Err(report) => eprint!("{}", report)
         }
     }
 }
@@ -70,10 +96,11 @@ impl ApplicationHandler for App {
                     size.width, size.height);
             },
             WindowEvent::RedrawRequested => {
-                let frame_time = chrono::Utc::now();
-                let time = frame_time - self.start_time;
+                self.reload_script();
+
+                let time = self.clock.seconds();
 
-                let audio_packet = self.audio_source.get_frame_data(time.as_seconds_f32());
+                let audio_packet = self.audio_source.get_frame_data(time, self.display.per_channel());
 
                 self.display.render(&self.renderer, &audio_packet, &mut self.window.as_mut().unwrap().surface);
 
@@ -0,0 +1,89 @@
+use std::sync::{
+    Arc,
+    atomic::{AtomicUsize, Ordering}
+};
+
+/// Tracks the position of audio playback.
+/// It is advanced by the player as samples are consumed by the output.
+#[derive(Clone)]
+pub struct PlaybackClock {
+    /// Number of interleaved samples consumed so far.
+    samples: Arc<AtomicUsize>,
+    samples_per_second: f64,
+    /// Time subtracted from the playback position, in seconds.
+    latency: f32,
+}
+
+impl PlaybackClock {
+    pub fn new(channels: u16, sample_rate: u32) -> Self {
+        Self {
+            samples: Arc::new(AtomicUsize::new(0)),
+            samples_per_second: (channels as f64) * (sample_rate as f64),
+            latency: 0.0
+        }
+    }
+
+    /// Set the time between samples being consumed and being heard, in seconds.
+    pub fn with_latency(mut self, latency: f32) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    /// Get the current playback time, in seconds.
+    /// This stays at 0 until the latency has passed.
+    pub fn seconds(&self) -> f32 {
+        let samples = self.samples.load(Ordering::Relaxed);
+        (((samples as f64) / self.samples_per_second) as f32 - self.latency).max(0.0)
+    }
+
+    pub(super) fn set_position(&self, samples: usize) {
+        self.samples.store(samples, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stereo_counts_two_samples_per_frame() {
+        let clock = PlaybackClock::new(2, 44100);
+        assert_eq!(clock.seconds(), 0.0);
+        clock.set_position(44100);
+        assert_eq!(clock.seconds(), 0.5);
+        clock.set_position(44100 * 2 * 3);
+        assert_eq!(clock.seconds(), 3.0);
+    }
+
+    #[test]
+    fn mono_counts_one_sample_per_frame() {
+        let clock = PlaybackClock::new(1, 48000);
+        clock.set_position(12000);
+        assert_eq!(clock.seconds(), 0.25);
+    }
+
+    #[test]
+    fn clones_share_the_position() {
+        let clock = PlaybackClock::new(2, 44100);
+        let player_clock = clock.clone();
+        player_clock.set_position(88200);
+        assert_eq!(clock.seconds(), 1.0);
+    }
+
+    #[test]
+    fn latency_delays_the_time() {
+        let clock = PlaybackClock::new(2, 44100).with_latency(0.25);
+        clock.set_position(88200);
+        assert_eq!(clock.seconds(), 0.75);
+    }
+
+    #[test]
+    fn time_is_clamped_until_latency_has_passed() {
+        let clock = PlaybackClock::new(2, 44100).with_latency(0.25);
+        assert_eq!(clock.seconds(), 0.0);
+        clock.set_position(22050);
+        assert_eq!(clock.seconds(), 0.0);
+        clock.set_position(44100);
+        assert_eq!(clock.seconds(), 0.25);
+    }
+}
@@ -0,0 +1,83 @@
+use std::{fs::File, path::Path};
+
+use symphonia::core::{
+    formats::FormatOptions,
+    io::MediaSourceStream,
+    meta::{MetadataOptions, MetadataRevision, StandardTagKey},
+    probe::Hint
+};
+
+/// Information about the track, read from the tags of the audio file.
+#[derive(Default)]
+pub struct Metadata {
+    title: Option<String>,
+    artist: Option<String>,
+    album: Option<String>
+}
+
+impl Metadata {
+    /// Read the tags of an audio file. Tags that can't be read are left empty.
+    /// If the file has no title, the file name is used instead.
+    pub fn read(path: &str) -> Self {
+        let mut metadata = Self::read_tags(path).unwrap_or_default();
+        if metadata.title.is_none() {
+            metadata.title = Path::new(path).file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned());
+        }
+        metadata
+    }
+
+    fn read_tags(path: &str) -> Option<Self> {
+        let file = File::open(path).ok()?;
+        let stream = MediaSourceStream::new(Box::new(file), Default::default());
+        let mut hint = Hint::new();
+        if let Some(extension) = Path::new(path).extension().and_then(|e| e.to_str()) {
+            hint.with_extension(extension);
+        }
+        let mut probed = symphonia::default::get_probe()
+            .format(&hint, stream, &FormatOptions::default(), &MetadataOptions::default())
+            .ok()?;
+
+        // Tags can be before the container (such as ID3), or inside it.
+        let mut metadata = Self::default();
+        if let Some(revision) = probed.metadata.get().as_ref().and_then(|m| m.current()) {
+            metadata.add_tags(revision);
+        }
+        if let Some(revision) = probed.format.metadata().current() {
+            metadata.add_tags(revision);
+        }
+        Some(metadata)
+    }
+
+    fn add_tags(&mut self, revision: &MetadataRevision) {
+        for tag in revision.tags() {
+            let field = match tag.std_key {
+                Some(StandardTagKey::TrackTitle) => &mut self.title,
+                Some(StandardTagKey::Artist) => &mut self.artist,
+                Some(StandardTagKey::Album) => &mut self.album,
+                _ => continue
+            };
+            // Some formats store strings with a null terminator.
+            field.get_or_insert_with(|| tag.value.to_string().trim_end_matches('\0').to_string());
+        }
+    }
+
+    /// Get a field, or an empty string if it is unknown.
+    pub fn get(&self, field: MetadataField) -> &str {
+        let value = match field {
+            MetadataField::Title => &self.title,
+            MetadataField::Artist => &self.artist,
+            MetadataField::Album => &self.album,
+        };
+        value.as_deref().unwrap_or("")
+    }
+}
+
+/// A field of the track metadata, which can be inserted into text.
+#[derive(Clone, Copy, Debug, strum::EnumString, strum::Display)]
+#[strum(serialize_all = "lowercase")]
+pub enum MetadataField {
+    Title,
+    Artist,
+    Album
+}
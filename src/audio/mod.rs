@@ -0,0 +1,464 @@
+mod spectrum;
+mod onset;
+mod tempo;
+mod store;
+mod clock;
+mod wav;
+mod metadata;
+
+use std::{fs::File, rc::Rc};
+use rodio::{Sink, Decoder, OutputStream};
+
+use spectrum::{SpectrumAnalyser, BASS_BAND, MID_BAND, TREBLE_BAND};
+pub use spectrum::Spectrum;
+use onset::{Envelope, Onsets};
+use tempo::Tempo;
+pub use store::SampleStore;
+pub use clock::PlaybackClock;
+pub use metadata::{Metadata, MetadataField};
+
+const FRAME_SIZE: f32 = 0.5; // seconds
+/// Number of points in the waveform of each frame.
+pub const WAVEFORM_SIZE: usize = 1024;
+
+/// An audio source file.
+pub struct AudioSource {
+    store: SampleStore,
+    frame_count: usize,
+    analyser: SpectrumAnalyser,
+    onsets: Onsets,
+    tempo: Tempo,
+    metadata: Rc<Metadata>,
+    /// Time of the previously requested frame.
+    last_time: Option<f32>,
+}
+
+impl AudioSource {
+    // TODO: path arg should be a path type.
+    pub fn from_file(path: &str) -> Self {
+        let file = File::open(path).expect("couldn't open audio file");
+        let source = Decoder::try_from(file).unwrap();
+
+        Self {
+            metadata: Rc::new(Metadata::read(path)),
+            ..Self::from_store(SampleStore::decode(source))
+        }
+    }
+
+    /// Analyse audio that has already been decoded.
+    pub fn from_store(store: SampleStore) -> Self {
+        let frame_count = ((store.sample_rate() as f32) * FRAME_SIZE).round() as usize;
+        let analyser = SpectrumAnalyser::new(frame_count, store.sample_rate());
+        let envelope = Envelope::from_samples(store.samples(), store.channels() as usize, store.sample_rate());
+        let onsets = Onsets::detect(&envelope);
+        let tempo = Tempo::estimate(&envelope, &onsets);
+
+        Self {
+            store,
+            frame_count,
+            analyser,
+            onsets,
+            tempo,
+            metadata: Rc::default(),
+            last_time: None
+        }
+    }
+
+    /// Get the length of the song, in frames of samples.
+    pub fn length(&self) -> usize {
+        self.store.frame_count()
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.store.sample_rate()
+    }
+
+    /// Write the decoded audio to a WAV file, cut or padded to the specified length in seconds.
+    pub fn write_wav(&self, writer: impl std::io::Write, seconds: f64) -> std::io::Result<()> {
+        let frame_count = (seconds * (self.store.sample_rate() as f64)).round() as usize;
+        wav::write_wav(&self.store, frame_count, writer)
+    }
+
+    /// Get a frame of audio for a specified time in the song, defined in seconds.
+    /// The left and right channels are only analysed separately if `per_channel` is set.
+    pub fn get_frame_data(&mut self, seconds: f32, per_channel: bool) -> AudioPacket {
+        // Time since the previous frame. Going backwards resets any smoothing.
+        let last_time = self.last_time.replace(seconds);
+        let delta_time = match last_time {
+            Some(last_time) if last_time < seconds => seconds - last_time,
+            _ => 0.0
+        };
+        let beat_info = self.get_beat_info(seconds, last_time);
+
+        let start_time = seconds - FRAME_SIZE * 0.5;
+        let frame_start = ((self.store.sample_rate() as f32) * start_time).round() as isize;
+
+        let channel_count = self.store.channels() as usize;
+
+        let (offset, samples) = self.store.frames(frame_start, self.frame_count);
+
+        let both = {
+            // Mix down to mono for spectrum analysis.
+            let mono = samples.chunks(channel_count)
+                .map(|frame| frame.iter().sum::<f32>() / (channel_count as f32))
+                .collect::<Vec<_>>();
+            ChannelData {
+                amplitude: mean_abs(&samples),
+                spectrum: self.analyser.analyse(offset, &mono),
+                waveform: decimate(offset, self.frame_count, &mono)
+            }
+        };
+
+        let mut packet = AudioPacket {
+            left: None,
+            right: None,
+            both,
+            balance: 0.0,
+            correlation: 1.0,
+            beat_info,
+            time: seconds,
+            delta_time,
+            metadata: self.metadata.clone()
+        };
+        if channel_count == 1 {
+            return packet;
+        }
+
+        (packet.balance, packet.correlation) = stereo_image(&samples, channel_count);
+        if per_channel {
+            let mut analyse = |channel: usize| {
+                let samples = samples.chunks_exact(channel_count).map(|frame| frame[channel]).collect::<Vec<_>>();
+                ChannelData {
+                    amplitude: mean_abs(&samples),
+                    spectrum: self.analyser.analyse(offset, &samples),
+                    waveform: decimate(offset, self.frame_count, &samples)
+                }
+            };
+            packet.left = Some(analyse(0));
+            packet.right = Some(analyse(1));
+        }
+        packet
+    }
+
+    /// Find the onsets relative to the specified time.
+    /// A beat is reported if an onset occurred since the previous frame.
+    fn get_beat_info(&self, seconds: f32, last_time: Option<f32>) -> BeatInfo {
+        let beat_count = self.onsets.count_at(seconds);
+        let beat = match last_time {
+            Some(last_time) if last_time < seconds => beat_count > self.onsets.count_at(last_time),
+            _ => false
+        };
+
+        let since_beat = beat_count.checked_sub(1)
+            .and_then(|index| self.onsets.time(index))
+            .map_or(seconds, |onset| seconds - onset);
+
+        BeatInfo {
+            beat,
+            since_beat,
+            beat_count,
+            bpm: self.tempo.bpm(),
+            beat_position: self.tempo.beat_position(seconds)
+        }
+    }
+}
+
+/// Audio data for a single frame.
+pub struct AudioPacket {
+    /// Only analysed if a single channel is needed. Otherwise, `both` is used instead.
+    left: Option<ChannelData>,
+    right: Option<ChannelData>,
+    both: ChannelData,
+    balance: f32,
+    correlation: f32,
+    beat_info: BeatInfo,
+    time: f32,
+    /// Time since the previous frame, in seconds.
+    delta_time: f32,
+    metadata: Rc<Metadata>
+}
+
+/// Audio data for a single channel in a frame.
+struct ChannelData {
+    amplitude: f32,
+    spectrum: Spectrum,
+    /// Samples over the frame, reduced to WAVEFORM_SIZE points.
+    waveform: Vec<f32>
+}
+
+/// Onset data for a single frame.
+struct BeatInfo {
+    /// True if an onset occurred during this frame.
+    beat: bool,
+    since_beat: f32,
+    beat_count: usize,
+    bpm: f32,
+    /// Number of beats since the start of the beat grid.
+    beat_position: f32
+}
+
+impl AudioPacket {
+    fn get_channel(&self, channel: Channel) -> &ChannelData {
+        let data = match channel {
+            Channel::Left => self.left.as_ref(),
+            Channel::Right => self.right.as_ref(),
+            Channel::Both => None,
+        };
+        data.unwrap_or(&self.both)
+    }
+
+    pub fn get_param(&self, param: AudioParam, channel: Channel) -> f32 {
+        use AudioParam::*;
+        let data = self.get_channel(channel);
+        match param {
+            Amplitude => data.amplitude,
+            Bass => data.spectrum.band(BASS_BAND.0, BASS_BAND.1),
+            Mid => data.spectrum.band(MID_BAND.0, MID_BAND.1),
+            Treble => data.spectrum.band(TREBLE_BAND.0, TREBLE_BAND.1),
+            Band(lo, hi) => data.spectrum.band(lo, hi),
+            Balance => self.balance,
+            Correlation => self.correlation,
+            Beat => if self.beat_info.beat { 1.0 } else { 0.0 },
+            SinceBeat => self.beat_info.since_beat,
+            BeatCount => self.beat_info.beat_count as f32,
+            Bpm => self.beat_info.bpm,
+            BeatPhase => self.beat_info.beat_position.rem_euclid(1.0),
+            BarPhase(beats) => (self.beat_info.beat_position / beats).rem_euclid(1.0),
+            Time => self.time
+        }
+    }
+
+    pub fn get_spectrum(&self, channel: Channel) -> &Spectrum {
+        &self.get_channel(channel).spectrum
+    }
+
+    /// Get the time since the previous frame, in seconds.
+    pub fn delta_time(&self) -> f32 {
+        self.delta_time
+    }
+
+    pub fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
+    /// Get the waveform of the frame, as WAVEFORM_SIZE samples.
+    pub fn get_waveform(&self, channel: Channel) -> &[f32] {
+        &self.get_channel(channel).waveform
+    }
+}
+
+#[derive(Clone, Copy, strum::EnumString, strum::Display)]
+pub enum Channel {
+    #[strum(ascii_case_insensitive)]
+    Left,
+    #[strum(ascii_case_insensitive)]
+    Right,
+    #[strum(ascii_case_insensitive)]
+    Both
+    // Future: more than 2 channel audio?
+}
+
+#[derive(Clone, Copy, strum::EnumString, strum::Display)]
+pub enum AudioParam {
+    #[strum(serialize = "level")]
+    Amplitude,
+    #[strum(ascii_case_insensitive)]
+    Bass,
+    #[strum(ascii_case_insensitive)]
+    Mid,
+    #[strum(ascii_case_insensitive)]
+    Treble,
+    /// Magnitude of the spectrum between two frequencies, in Hz.
+    #[strum(disabled)]
+    Band(f32, f32),
+    /// Stereo balance, from -1.0 (left) to 1.0 (right).
+    #[strum(ascii_case_insensitive)]
+    Balance,
+    /// Correlation of left and right channels, from -1.0 to 1.0.
+    #[strum(ascii_case_insensitive)]
+    Correlation,
+    /// 1.0 on the frame of an onset, 0.0 otherwise.
+    #[strum(ascii_case_insensitive)]
+    Beat,
+    #[strum(serialize = "since_beat")]
+    SinceBeat,
+    #[strum(serialize = "beat_count")]
+    BeatCount,
+    #[strum(ascii_case_insensitive)]
+    Bpm,
+    /// Position within the current beat, from 0.0 to 1.0.
+    #[strum(serialize = "beat_phase")]
+    BeatPhase,
+    /// Position within the current bar of the specified number of beats, from 0.0 to 1.0.
+    #[strum(disabled)]
+    BarPhase(f32),
+    #[strum(ascii_case_insensitive)]
+    Time,
+}
+
+impl AudioParam {
+    /// Whether the parameter is measured separately for each channel, so that it can be restricted to one.
+    pub fn is_per_channel(self) -> bool {
+        use AudioParam::*;
+        matches!(self, Amplitude | Bass | Mid | Treble | Band(..))
+    }
+}
+
+fn mean_abs(samples: &[f32]) -> f32 {
+    samples.iter()
+        .fold(0.0, |acc, n| acc + n.abs()) / (samples.len().max(1) as f32)
+}
+
+/// Reduce a frame of samples, which begin `offset` samples into the frame, to WAVEFORM_SIZE points.
+/// Each point is the sample with the largest magnitude in its section of the frame.
+fn decimate(offset: usize, frame_count: usize, samples: &[f32]) -> Vec<f32> {
+    (0..WAVEFORM_SIZE)
+        .map(|point| {
+            let start = point * frame_count / WAVEFORM_SIZE;
+            let end = ((point + 1) * frame_count / WAVEFORM_SIZE).max(start + 1);
+            let start = start.saturating_sub(offset).min(samples.len());
+            let end = end.saturating_sub(offset).min(samples.len());
+            samples[start..end].iter()
+                .copied()
+                .max_by(|a, b| a.abs().total_cmp(&b.abs()))
+                .unwrap_or(0.0)
+        })
+        .collect()
+}
+
+/// Calculate the balance and correlation of the first two channels of interleaved samples.
+fn stereo_image(samples: &[f32], channel_count: usize) -> (f32, f32) {
+    let (lr, ll, rr) = samples.chunks_exact(channel_count)
+        .fold((0.0, 0.0, 0.0), |(lr, ll, rr), frame| {
+            let (l, r) = (frame[0], frame[1]);
+            (lr + l * r, ll + l * l, rr + r * r)
+        });
+
+    // The balance of the RMS levels. Both are divided by the same number of samples, so that can be skipped.
+    let (left, right) = (ll.sqrt(), rr.sqrt());
+    let balance = if left + right > 0.0 {
+        (right - left) / (right + left)
+    } else {
+        0.0
+    };
+
+    let denom = (ll * rr).sqrt();
+    let correlation = if denom > 0.0 {
+        lr / denom
+    } else {
+        0.0
+    };
+    (balance, correlation)
+}
+
+/// Handles playback of the audio source to speakers.
+pub struct AudioPlayer {
+    output: OutputStream,
+    _sink: Sink
+}
+
+impl AudioPlayer {
+    pub fn new() -> Self {
+        let stream_handle = rodio::OutputStreamBuilder::open_default_stream()
+            .expect("could not find audio output");
+        let sink = rodio::Sink::connect_new(stream_handle.mixer());
+
+        Self {
+            output: stream_handle,
+            _sink: sink
+        }
+    }
+
+    /// Start playing the source.
+    /// Returns a clock that follows the playback position.
+    pub fn play(&self, source: &AudioSource) -> PlaybackClock {
+        let clock = PlaybackClock::new(source.store.channels(), source.store.sample_rate());
+        self.output.mixer().add(source.store.player(clock.clone()));
+        clock
+    }
+}
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// The frame at 10 seconds into a 120 BPM click track, with the metadata of a file that doesn't exist.
+    pub(crate) fn packet_at_120_bpm(path: &str) -> AudioPacket {
+        let clicks = (0..30).map(|n| 0.25 + (n as f32) * 0.5).collect::<Vec<_>>();
+        let samples = onset::tests::click_track(&clicks, 15.0, 1.0);
+        let mut source = AudioSource::from_store(SampleStore::new(samples, 1, 44100));
+        source.metadata = Rc::new(Metadata::read(path));
+        source.get_frame_data(10.0, false)
+    }
+
+    #[test]
+    fn beat_params_step_on_the_frame_of_each_onset() {
+        // Clicks part of the way through frames 16, 31 and 52 at 30 fps.
+        let samples = onset::tests::click_track(&[0.51, 1.01, 1.71], 2.0, 1.0);
+        let mut source = AudioSource::from_store(SampleStore::new(samples, 1, 44100));
+
+        let mut beats = Vec::new();
+        for frame in 0..60 {
+            let packet = source.get_frame_data((frame as f32) / 30.0, false);
+            let beat_count = packet.get_param(AudioParam::BeatCount, Channel::Both);
+            let since_beat = packet.get_param(AudioParam::SinceBeat, Channel::Both);
+            if packet.get_param(AudioParam::Beat, Channel::Both) == 1.0 {
+                beats.push(frame);
+                // The onset is within a hop of the click, and the frame is less than a frame after it.
+                assert!(since_beat < 1.0 / 30.0 + 0.012, "since_beat {} on frame {}", since_beat, frame);
+            }
+            assert_eq!(beat_count, beats.len() as f32, "beat_count on frame {}", frame);
+            if beats.is_empty() {
+                assert_eq!(since_beat, (frame as f32) / 30.0);
+            }
+        }
+        assert_eq!(beats, [16, 31, 52]);
+    }
+
+    #[test]
+    fn phase_params_wrap_on_each_beat() {
+        // 120 BPM, so a beat every 0.5 seconds.
+        let clicks = (0..40).map(|n| 0.25 + (n as f32) * 0.5).collect::<Vec<_>>();
+        let samples = onset::tests::click_track(&clicks, 20.5, 1.0);
+        let mut source = AudioSource::from_store(SampleStore::new(samples, 1, 44100));
+        let mut param_at = |param, seconds| source.get_frame_data(seconds, false).get_param(param, Channel::Both);
+
+        for click in &clicks[4..35] {
+            let before = param_at(AudioParam::BeatPhase, click - 0.02);
+            let after = param_at(AudioParam::BeatPhase, click + 0.02);
+            assert!(before > 0.9 && after < 0.1, "beat phase {} before and {} after click at {}", before, after, click);
+        }
+
+        // Bars of 3 beats wrap on every third click.
+        let bar_phases = clicks[4..35].iter()
+            .map(|click| param_at(AudioParam::BarPhase(3.0), click + 0.02))
+            .collect::<Vec<_>>();
+        for pair in bar_phases.windows(2) {
+            let step = (pair[1] - pair[0]).rem_euclid(1.0);
+            assert!((step - 1.0 / 3.0).abs() < 0.05, "bar phase went from {} to {}", pair[0], pair[1]);
+        }
+        let wraps = bar_phases.windows(2).filter(|pair| pair[1] < pair[0]).count();
+        assert_eq!(wraps, (bar_phases.len() - 1) / 3);
+    }
+
+    #[test]
+    fn channels_are_only_analysed_when_needed() {
+        // A tone on the left channel only.
+        let samples = (0..44100)
+            .flat_map(|n| [(n as f32 * 0.05).sin() * 0.5, 0.0]);
+        let mut source = AudioSource::from_store(SampleStore::new(samples, 2, 44100));
+
+        let packet = source.get_frame_data(0.5, true);
+        let level = |channel| packet.get_param(AudioParam::Amplitude, channel);
+        assert!(level(Channel::Left) > 0.3);
+        assert_eq!(level(Channel::Right), 0.0);
+        assert!((level(Channel::Both) - level(Channel::Left) * 0.5).abs() < 0.01);
+        assert_eq!(packet.get_param(AudioParam::Balance, Channel::Both), -1.0);
+
+        // Without separate analysis, each channel gives the mix, but stereo parameters are still measured.
+        let packet = source.get_frame_data(0.5, false);
+        let level = |channel| packet.get_param(AudioParam::Amplitude, channel);
+        assert_eq!(level(Channel::Left), level(Channel::Both));
+        assert_eq!(level(Channel::Right), level(Channel::Both));
+        assert_eq!(packet.get_param(AudioParam::Balance, Channel::Both), -1.0);
+    }
+}
@@ -0,0 +1,234 @@
+use rustfft::{FftPlanner, num_complex::Complex};
+
+const WINDOW_SIZE: usize = 1024;
+const HOP_SIZE: usize = 512;
+
+/// Number of hops either side used to compute the adaptive threshold.
+const THRESHOLD_RADIUS: usize = 16;
+/// Number of hops either side that an onset must be the peak of.
+const PEAK_RADIUS: usize = 3;
+const THRESHOLD_MULTIPLIER: f32 = 1.5;
+const THRESHOLD_OFFSET: f32 = 0.05;
+/// Minimum time between two onsets, in seconds.
+const MIN_INTERVAL: f32 = 0.1;
+
+/// Spectral flux envelope of an entire track.
+pub struct Envelope {
+    /// Spectral flux of each hop.
+    flux: Vec<f32>,
+    /// Time between hops, in seconds.
+    hop_time: f32,
+    /// Time of the first hop, in seconds.
+    offset: f32,
+}
+
+impl Envelope {
+    /// Calculate the envelope of a stream of interleaved samples.
+    pub fn from_samples(samples: impl Iterator<Item = f32>, channels: usize, sample_rate: u32) -> Self {
+        Self {
+            flux: spectral_flux(samples, channels),
+            hop_time: (HOP_SIZE as f32) / (sample_rate as f32),
+            // Report times at the centre of the window.
+            offset: (WINDOW_SIZE as f32) * 0.5 / (sample_rate as f32),
+        }
+    }
+
+    pub fn flux(&self) -> &[f32] {
+        &self.flux
+    }
+
+    pub fn hop_time(&self) -> f32 {
+        self.hop_time
+    }
+
+    /// Get the time of a (fractional) hop index, in seconds.
+    pub fn time_of(&self, index: f32) -> f32 {
+        index * self.hop_time + self.offset
+    }
+}
+
+/// Onsets detected over an entire track.
+pub struct Onsets {
+    /// Times of each onset, in seconds, in ascending order.
+    times: Vec<f32>,
+}
+
+impl Onsets {
+    /// Detect onsets in the envelope of a track.
+    /// Uses spectral flux with an adaptive threshold.
+    pub fn detect(envelope: &Envelope) -> Self {
+        let flux = envelope.flux();
+
+        let mut times = Vec::new();
+        for i in 0..flux.len() {
+            let threshold_start = i.saturating_sub(THRESHOLD_RADIUS);
+            let threshold_end = (i + THRESHOLD_RADIUS + 1).min(flux.len());
+            let threshold_window = &flux[threshold_start..threshold_end];
+            let mean = threshold_window.iter().sum::<f32>() / (threshold_window.len() as f32);
+            if flux[i] <= mean * THRESHOLD_MULTIPLIER + THRESHOLD_OFFSET {
+                continue;
+            }
+
+            let peak_start = i.saturating_sub(PEAK_RADIUS);
+            let peak_end = (i + PEAK_RADIUS + 1).min(flux.len());
+            if flux[peak_start..peak_end].iter().any(|f| *f > flux[i]) {
+                continue;
+            }
+
+            let time = envelope.time_of(i as f32);
+            if times.last().is_none_or(|last| time - last >= MIN_INTERVAL) {
+                times.push(time);
+            }
+        }
+
+        Self {
+            times
+        }
+    }
+
+    pub fn times(&self) -> &[f32] {
+        &self.times
+    }
+
+    /// Get the number of onsets at or before `seconds`.
+    pub fn count_at(&self, seconds: f32) -> usize {
+        self.times.partition_point(|t| *t <= seconds)
+    }
+
+    /// Get the time of the nth onset.
+    pub fn time(&self, index: usize) -> Option<f32> {
+        self.times.get(index).copied()
+    }
+}
+
+/// Calculate the spectral flux of each hop through the stream.
+fn spectral_flux(samples: impl Iterator<Item = f32>, channels: usize) -> Vec<f32> {
+    let fft = FftPlanner::new().plan_fft_forward(WINDOW_SIZE);
+    let window = (0..WINDOW_SIZE)
+        .map(|n| {
+            let phase = (n as f32) / (WINDOW_SIZE as f32) * (2.0 * std::f32::consts::PI);
+            0.5 - 0.5 * phase.cos()
+        })
+        .collect::<Vec<_>>();
+
+    let mut input = Vec::with_capacity(WINDOW_SIZE);
+    let mut buffer = vec![Complex::default(); WINDOW_SIZE];
+    let mut prev_magnitudes = vec![0.0; WINDOW_SIZE / 2];
+    let mut flux = Vec::new();
+
+    let mut frame = Vec::with_capacity(channels);
+    for sample in samples {
+        frame.push(sample);
+        if frame.len() < channels {
+            continue;
+        }
+        input.push(frame.iter().sum::<f32>() / (channels as f32));
+        frame.clear();
+
+        if input.len() < WINDOW_SIZE {
+            continue;
+        }
+
+        for ((out, sample), window) in buffer.iter_mut().zip(&input).zip(&window) {
+            *out = Complex::new(sample * window, 0.0);
+        }
+        fft.process(&mut buffer);
+
+        let mut total = 0.0;
+        for (prev, c) in prev_magnitudes.iter_mut().zip(&buffer) {
+            // Log compression makes quieter onsets easier to detect.
+            let magnitude = (1.0 + c.norm()).ln();
+            total += (magnitude - *prev).max(0.0);
+            *prev = magnitude;
+        }
+        flux.push(total);
+
+        input.drain(..HOP_SIZE);
+    }
+
+    flux
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 44100;
+
+    /// Mono audio of the specified length with a short, broadband click at each time.
+    pub(in crate::audio) fn click_track(times: &[f32], seconds: f32, volume: f32) -> Vec<f32> {
+        let mut samples = vec![0.0; (seconds * SAMPLE_RATE as f32) as usize];
+        for time in times {
+            let start = (time * SAMPLE_RATE as f32) as usize;
+            for (n, sample) in samples[start..].iter_mut().take(32).enumerate() {
+                let sign = if n % 2 == 0 { 1.0 } else { -1.0 };
+                *sample = sign * volume * (1.0 - (n as f32) / 32.0);
+            }
+        }
+        samples
+    }
+
+    fn detect(samples: Vec<f32>) -> Onsets {
+        Onsets::detect(&Envelope::from_samples(samples.into_iter(), 1, SAMPLE_RATE))
+    }
+
+    fn hop_time() -> f32 {
+        (HOP_SIZE as f32) / (SAMPLE_RATE as f32)
+    }
+
+    #[test]
+    fn clicks_are_onsets() {
+        let clicks = [0.5, 1.0, 1.7, 2.2, 2.45];
+        let onsets = detect(click_track(&clicks, 3.0, 1.0));
+        assert_eq!(onsets.times().len(), clicks.len(), "{:?}", onsets.times());
+        for (onset, click) in onsets.times().iter().zip(clicks) {
+            assert!((onset - click).abs() < hop_time(), "onset at {} for click at {}", onset, click);
+        }
+    }
+
+    #[test]
+    fn silence_has_no_onsets() {
+        assert!(detect(vec![0.0; SAMPLE_RATE as usize * 3]).times().is_empty());
+    }
+
+    #[test]
+    fn steady_tone_has_no_onsets_after_it_starts() {
+        // Calculate the phase in f64, as f32 loses enough precision over a few seconds to add noise.
+        let tone = (0..(SAMPLE_RATE as usize * 3))
+            .map(|n| ((2.0 * std::f64::consts::PI * 440.0 * (n as f64) / (SAMPLE_RATE as f64)).sin() * 0.5) as f32)
+            .collect();
+        let onsets = detect(tone);
+        assert!(onsets.times().iter().all(|time| *time <= hop_time()), "{:?}", onsets.times());
+    }
+
+    #[test]
+    fn quiet_clicks_are_below_threshold() {
+        // Flux must exceed THRESHOLD_OFFSET even in silence.
+        assert!(detect(click_track(&[0.5, 1.0], 1.5, 0.000001)).times().is_empty());
+    }
+
+    #[test]
+    fn close_onsets_are_merged() {
+        // Further apart than PEAK_RADIUS hops, but closer than MIN_INTERVAL.
+        let onsets = detect(click_track(&[0.5, 0.57], 1.0, 1.0));
+        assert_eq!(onsets.times().len(), 1, "{:?}", onsets.times());
+
+        let onsets = detect(click_track(&[0.5, 0.65], 1.0, 1.0));
+        assert_eq!(onsets.times().len(), 2, "{:?}", onsets.times());
+    }
+
+    #[test]
+    fn count_steps_at_each_onset() {
+        let onsets = Onsets {
+            times: vec![0.5, 1.0, 1.5]
+        };
+        assert_eq!(onsets.count_at(0.0), 0);
+        assert_eq!(onsets.count_at(0.499), 0);
+        assert_eq!(onsets.count_at(0.5), 1);
+        assert_eq!(onsets.count_at(0.999), 1);
+        assert_eq!(onsets.count_at(1.0), 2);
+        assert_eq!(onsets.count_at(10.0), 3);
+        assert_eq!(onsets.time(1), Some(1.0));
+        assert_eq!(onsets.time(3), None);
+    }
+}
@@ -0,0 +1,157 @@
+use std::sync::Arc;
+use rustfft::{Fft, FftPlanner, num_complex::Complex};
+
+/// Frequency ranges of the named bands, in Hz.
+pub const BASS_BAND: (f32, f32) = (20.0, 250.0);
+pub const MID_BAND: (f32, f32) = (250.0, 4000.0);
+pub const TREBLE_BAND: (f32, f32) = (4000.0, 20000.0);
+
+/// Computes magnitude spectra for frames of audio.
+pub struct SpectrumAnalyser {
+    fft: Arc<dyn Fft<f32>>,
+    window: Vec<f32>,
+    window_sum: f32,
+    buffer: Vec<Complex<f32>>,
+    bin_width: f32,
+}
+
+impl SpectrumAnalyser {
+    /// Create an analyser for frames of `frame_size` samples.
+    /// The FFT is zero-padded up to the next power of two.
+    pub fn new(frame_size: usize, sample_rate: u32) -> Self {
+        let fft_size = frame_size.next_power_of_two();
+        let fft = FftPlanner::new().plan_fft_forward(fft_size);
+
+        // Hann window over the frame.
+        let window = (0..frame_size)
+            .map(|n| {
+                let phase = (n as f32) / (frame_size as f32) * (2.0 * std::f32::consts::PI);
+                0.5 - 0.5 * phase.cos()
+            })
+            .collect::<Vec<_>>();
+        let window_sum = window.iter().sum();
+
+        Self {
+            fft,
+            window,
+            window_sum,
+            buffer: vec![Complex::default(); fft_size],
+            bin_width: (sample_rate as f32) / (fft_size as f32),
+        }
+    }
+
+    /// Analyse a frame of mono samples, which begin `offset` samples into the frame.
+    /// Missing samples at the start and end of the frame are treated as silence.
+    pub fn analyse(&mut self, offset: usize, samples: &[f32]) -> Spectrum {
+        self.buffer.fill(Complex::default());
+        let frame = self.buffer.iter_mut().zip(&self.window).skip(offset);
+        for ((out, window), sample) in frame.zip(samples) {
+            out.re = sample * window;
+        }
+
+        self.fft.process(&mut self.buffer);
+
+        // Scale so that a full-scale sine wave peaks at 1.0.
+        let scale = 2.0 / self.window_sum;
+        let magnitudes = self.buffer[..(self.buffer.len() / 2)].iter()
+            .map(|c| c.norm() * scale)
+            .collect();
+
+        Spectrum {
+            magnitudes,
+            bin_width: self.bin_width
+        }
+    }
+}
+
+/// Magnitude spectrum of a single frame.
+#[derive(Clone, Default)]
+pub struct Spectrum {
+    magnitudes: Vec<f32>,
+    bin_width: f32,
+}
+
+impl Spectrum {
+    /// Get the combined magnitude of all bins between `lo` and `hi` Hz.
+    pub fn band(&self, lo: f32, hi: f32) -> f32 {
+        if self.bin_width <= 0.0 {
+            return 0.0;
+        }
+        let start = ((lo / self.bin_width).ceil().max(0.0) as usize).min(self.magnitudes.len());
+        let end = ((hi / self.bin_width).ceil().max(0.0) as usize).min(self.magnitudes.len());
+        if start >= end {
+            return 0.0;
+        }
+        self.magnitudes[start..end].iter()
+            .map(|m| m * m)
+            .sum::<f32>()
+            .sqrt()
+    }
+
+    /// Get the largest magnitude of the bins between `lo` and `hi` Hz.
+    /// If the range is narrower than a bin, the nearest bin is used.
+    pub fn peak(&self, lo: f32, hi: f32) -> f32 {
+        if self.bin_width <= 0.0 || self.magnitudes.is_empty() {
+            return 0.0;
+        }
+        let last = self.magnitudes.len() - 1;
+        let start = ((lo / self.bin_width).ceil().max(0.0) as usize).min(last);
+        let end = ((hi / self.bin_width).floor().max(0.0) as usize).min(last);
+        if start > end {
+            let nearest = (((lo + hi) * 0.5 / self.bin_width).round().max(0.0) as usize).min(last);
+            return self.magnitudes[nearest];
+        }
+        self.magnitudes[start..=end].iter()
+            .copied()
+            .fold(0.0, f32::max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 48000;
+    const FRAME_SIZE: usize = 1024;
+
+    /// A full-scale sine wave whose frequency is in the middle of bin `bin`.
+    fn sine_spectrum(bin: usize) -> Spectrum {
+        let frequency = (bin as f32) * (SAMPLE_RATE as f32) / (FRAME_SIZE as f32);
+        let samples = (0..FRAME_SIZE)
+            .map(|n| (2.0 * std::f32::consts::PI * frequency * (n as f32) / (SAMPLE_RATE as f32)).sin())
+            .collect::<Vec<_>>();
+        SpectrumAnalyser::new(FRAME_SIZE, SAMPLE_RATE).analyse(0, &samples)
+    }
+
+    #[test]
+    fn sine_is_in_its_band() {
+        // 32 bins of 46.875 Hz is 1500 Hz.
+        let spectrum = sine_spectrum(32);
+        assert!(spectrum.band(MID_BAND.0, MID_BAND.1) > 0.9);
+        assert!(spectrum.band(BASS_BAND.0, BASS_BAND.1) < 0.01);
+        assert!(spectrum.band(TREBLE_BAND.0, TREBLE_BAND.1) < 0.01);
+    }
+
+    #[test]
+    fn full_scale_sine_peaks_at_one() {
+        let spectrum = sine_spectrum(32);
+        assert!((spectrum.peak(1000.0, 2000.0) - 1.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn empty_and_out_of_range_bands_are_silent() {
+        let spectrum = sine_spectrum(32);
+        assert_eq!(spectrum.band(2000.0, 1000.0), 0.0);
+        assert_eq!(spectrum.band(1500.0, 1500.0), 0.0);
+        assert_eq!(spectrum.band(30000.0, 40000.0), 0.0);
+        assert_eq!(Spectrum::default().band(20.0, 20000.0), 0.0);
+        assert_eq!(Spectrum::default().peak(20.0, 20000.0), 0.0);
+    }
+
+    #[test]
+    fn narrow_peak_uses_nearest_bin() {
+        let spectrum = sine_spectrum(32);
+        // Narrower than a bin, and between the bin edges around 1500 Hz.
+        assert!((spectrum.peak(1499.0, 1501.0) - 1.0).abs() < 0.01);
+    }
+}
@@ -0,0 +1,192 @@
+use std::{sync::Arc, time::Duration};
+use rodio::{Source, ChannelCount, SampleRate};
+
+use super::PlaybackClock;
+
+/// Decoded audio, held in memory for random access.
+/// Samples are stored with 16 bits, so an hour of 44.1 kHz stereo audio takes about 635 MB.
+pub struct SampleStore {
+    /// Interleaved samples. Kept in a Vec, so that they aren't copied into the Arc.
+    samples: Arc<Vec<i16>>,
+    channels: ChannelCount,
+    sample_rate: SampleRate,
+}
+
+impl SampleStore {
+    /// Create a store from interleaved samples. Samples outside of -1.0 to 1.0 are clipped.
+    pub fn new(samples: impl IntoIterator<Item = f32>, channels: ChannelCount, sample_rate: SampleRate) -> Self {
+        Self {
+            samples: Arc::new(samples.into_iter().map(to_pcm).collect()),
+            channels,
+            sample_rate
+        }
+    }
+
+    /// Decode an entire source into memory.
+    pub fn decode(source: impl Source) -> Self {
+        let channels = source.channels();
+        let sample_rate = source.sample_rate();
+        Self::new(source, channels, sample_rate)
+    }
+
+    pub fn channels(&self) -> ChannelCount {
+        self.channels
+    }
+
+    pub fn sample_rate(&self) -> SampleRate {
+        self.sample_rate
+    }
+
+    /// Get the length of the audio, in frames. Each frame has one sample for every channel.
+    pub fn frame_count(&self) -> usize {
+        self.samples.len() / (self.channels as usize)
+    }
+
+    /// All samples in the store, interleaved.
+    pub fn samples(&self) -> impl Iterator<Item = f32> + '_ {
+        self.samples.iter().copied().map(from_pcm)
+    }
+
+    /// Get the interleaved samples for `count` frames, starting at frame `start`.
+    /// Frames outside of the track are clipped, so fewer frames may be returned.
+    /// Also returns the number of frames that were clipped from the start.
+    pub fn frames(&self, start: isize, count: usize) -> (usize, Vec<f32>) {
+        let channels = self.channels as usize;
+        let total = self.samples.len() / channels;
+
+        let clipped = (-start).max(0) as usize;
+        let begin = start.max(0) as usize;
+        let end = (start + count as isize).max(0) as usize;
+        let begin = begin.min(total);
+        let end = end.min(total);
+
+        let samples = self.samples[(begin * channels)..(end * channels)].iter()
+            .copied()
+            .map(from_pcm)
+            .collect();
+        (clipped.min(count), samples)
+    }
+
+    /// Create a source that plays back the store from the start.
+    /// The player advances the clock as it is consumed.
+    pub fn player(&self, clock: PlaybackClock) -> StorePlayer {
+        StorePlayer {
+            samples: self.samples.clone(),
+            channels: self.channels,
+            sample_rate: self.sample_rate,
+            pos: 0,
+            clock
+        }
+    }
+}
+
+/// Plays back a sample store. Shares the samples with the store.
+pub struct StorePlayer {
+    samples: Arc<Vec<i16>>,
+    channels: ChannelCount,
+    sample_rate: SampleRate,
+    pos: usize,
+    clock: PlaybackClock,
+}
+
+impl Iterator for StorePlayer {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let sample = self.samples.get(self.pos).copied().map(from_pcm);
+        if sample.is_some() {
+            self.pos += 1;
+            self.clock.set_position(self.pos);
+        }
+        sample
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.samples.len().saturating_sub(self.pos);
+        (remaining, Some(remaining))
+    }
+}
+
+impl Source for StorePlayer {
+    fn current_span_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> ChannelCount {
+        self.channels
+    }
+
+    fn sample_rate(&self) -> SampleRate {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        let frames = (self.samples.len() / (self.channels as usize)) as f64;
+        Some(Duration::from_secs_f64(frames / (self.sample_rate as f64)))
+    }
+}
+
+fn to_pcm(sample: f32) -> i16 {
+    (sample.clamp(-1.0, 1.0) * (i16::MAX as f32)).round() as i16
+}
+
+fn from_pcm(sample: i16) -> f32 {
+    (sample as f32) / (i16::MAX as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A value that can be stored exactly, which is different for each of the first 30000 frames.
+    fn sample(frame: usize) -> f32 {
+        ((frame % 30000) as f32) / (i16::MAX as f32)
+    }
+
+    /// A stereo store where each sample depends on its frame index, negated on the right channel.
+    fn store(frame_count: usize) -> SampleStore {
+        let samples = (0..frame_count)
+            .flat_map(|frame| [sample(frame), -sample(frame)]);
+        SampleStore::new(samples, 2, 44100)
+    }
+
+    #[test]
+    fn frames_inside_track() {
+        let store = store(10);
+        assert_eq!(store.frames(3, 2), (0, vec![sample(3), -sample(3), sample(4), -sample(4)]));
+    }
+
+    #[test]
+    fn frames_before_start_are_clipped() {
+        let store = store(10);
+        assert_eq!(store.frames(-2, 4), (2, vec![sample(0), -sample(0), sample(1), -sample(1)]));
+        assert_eq!(store.frames(-10, 4), (4, vec![]));
+    }
+
+    #[test]
+    fn frames_after_end_are_clipped() {
+        let store = store(10);
+        assert_eq!(store.frames(8, 4), (0, vec![sample(8), -sample(8), sample(9), -sample(9)]));
+        assert_eq!(store.frames(20, 4), (0, vec![]));
+    }
+
+    #[test]
+    fn lookup_at_end_of_track() {
+        // A minute of stereo audio at 44.1 kHz.
+        let frame_count = 44100 * 60;
+        let store = store(frame_count);
+        let last = sample(frame_count - 1);
+        assert_eq!(store.frames(frame_count as isize - 1, 2), (0, vec![last, -last]));
+        assert_eq!(store.frame_count(), frame_count);
+        // Two bytes for each sample.
+        assert_eq!(std::mem::size_of_val(&store.samples[..]), frame_count * 2 * 2);
+    }
+
+    #[test]
+    fn samples_are_clipped_to_full_scale() {
+        let store = SampleStore::new([1.5, 1.0, -1.0, -2.0, 0.5], 1, 44100);
+        let samples = store.samples().collect::<Vec<_>>();
+        assert_eq!(samples[..4], [1.0, 1.0, -1.0, -1.0]);
+        assert!((samples[4] - 0.5).abs() < 1.0 / (i16::MAX as f32));
+    }
+}
@@ -0,0 +1,193 @@
+use super::onset::{Envelope, Onsets};
+
+const MIN_BPM: f32 = 60.0;
+const MAX_BPM: f32 = 200.0;
+/// Tempo that is preferred when choosing between multiples of the beat.
+const PREFERRED_BPM: f32 = 120.0;
+/// Width of the preference around PREFERRED_BPM, in octaves.
+const PREFERENCE_WIDTH: f32 = 1.0;
+/// Maximum distance of an onset from the beat grid to be used for refinement, as a fraction of a beat.
+const REFINE_TOLERANCE: f32 = 0.2;
+const REFINE_ITERATIONS: usize = 2;
+
+/// A global tempo estimate for a track.
+pub struct Tempo {
+    bpm: f32,
+    /// Time of a beat, in seconds. Other beats are at multiples of the period from here.
+    offset: f32,
+}
+
+impl Tempo {
+    /// Estimate the tempo from the autocorrelation of the onset envelope.
+    /// The beat grid is then fitted to the detected onsets.
+    pub fn estimate(envelope: &Envelope, onsets: &Onsets) -> Self {
+        let flux = envelope.flux();
+        if flux.is_empty() {
+            return Self::none();
+        }
+
+        // Remove the mean so that steady sections don't dominate.
+        let mean = flux.iter().sum::<f32>() / (flux.len() as f32);
+        let signal = flux.iter().map(|f| (f - mean).max(0.0)).collect::<Vec<_>>();
+
+        let lag_for_bpm = |bpm: f32| 60.0 / (bpm * envelope.hop_time());
+        let min_lag = lag_for_bpm(MAX_BPM).floor().max(1.0) as usize;
+        let max_lag = (lag_for_bpm(MIN_BPM).ceil() as usize).min(signal.len().saturating_sub(1));
+        if min_lag + 2 > max_lag {
+            return Self::none();
+        }
+
+        let autocorrelation = (0..=(max_lag + 1))
+            .map(|lag| {
+                signal.iter().zip(&signal[lag.min(signal.len())..])
+                    .map(|(a, b)| a * b)
+                    .sum::<f32>()
+            })
+            .collect::<Vec<_>>();
+
+        // Weight towards a typical tempo to avoid choosing half or double time.
+        let weighted = |lag: usize| {
+            let bpm = 60.0 / ((lag as f32) * envelope.hop_time());
+            let octaves = (bpm / PREFERRED_BPM).log2() / PREFERENCE_WIDTH;
+            autocorrelation[lag] * (-0.5 * octaves * octaves).exp()
+        };
+        let best_lag = (min_lag..=max_lag)
+            .max_by(|a, b| weighted(*a).total_cmp(&weighted(*b)))
+            .unwrap();
+        if autocorrelation[best_lag] <= 0.0 {
+            return Self::none();
+        }
+
+        // Refine the lag with parabolic interpolation.
+        let (prev, peak, next) = (autocorrelation[best_lag - 1], autocorrelation[best_lag], autocorrelation[best_lag + 1]);
+        let denom = prev - 2.0 * peak + next;
+        let adjustment = if denom < 0.0 { (0.5 * (prev - next) / denom).clamp(-0.5, 0.5) } else { 0.0 };
+        let period = (best_lag as f32) + adjustment;
+
+        // Find the phase of the beat grid that lines up with the most onset energy.
+        let best_phase = (0..best_lag)
+            .max_by(|a, b| {
+                let score = |phase: usize| (0..)
+                    .map(|n| ((phase as f32) + (n as f32) * period).round() as usize)
+                    .take_while(|i| *i < signal.len())
+                    .map(|i| signal[i])
+                    .sum::<f32>();
+                score(*a).total_cmp(&score(*b))
+            })
+            .unwrap();
+
+        let mut tempo = Self {
+            bpm: 60.0 / (period * envelope.hop_time()),
+            offset: envelope.time_of(best_phase as f32),
+        };
+        for _ in 0..REFINE_ITERATIONS {
+            tempo.refine(onsets);
+        }
+        tempo
+    }
+
+    /// Fit the beat grid to the onsets that lie close to it, using least squares.
+    fn refine(&mut self, onsets: &Onsets) {
+        let period = 60.0 / self.bpm;
+        let matched = onsets.times().iter()
+            .filter_map(|time| {
+                let beat = ((time - self.offset) / period).round();
+                let error = time - (self.offset + beat * period);
+                (error.abs() < period * REFINE_TOLERANCE).then_some((beat as f64, *time as f64))
+            })
+            .collect::<Vec<_>>();
+        if matched.len() < 2 {
+            return;
+        }
+
+        let count = matched.len() as f64;
+        let mean_beat = matched.iter().map(|(beat, _)| beat).sum::<f64>() / count;
+        let mean_time = matched.iter().map(|(_, time)| time).sum::<f64>() / count;
+        let (covariance, variance) = matched.iter()
+            .fold((0.0, 0.0), |(cov, var), (beat, time)| {
+                let beat_diff = beat - mean_beat;
+                (cov + beat_diff * (time - mean_time), var + beat_diff * beat_diff)
+            });
+        if variance <= 0.0 || covariance <= 0.0 {
+            return;
+        }
+
+        let period = covariance / variance;
+        self.bpm = (60.0 / period) as f32;
+        self.offset = (mean_time - period * mean_beat) as f32;
+    }
+
+    /// A tempo for tracks that have no detectable beat.
+    fn none() -> Self {
+        Self {
+            bpm: 0.0,
+            offset: 0.0
+        }
+    }
+
+    pub fn bpm(&self) -> f32 {
+        self.bpm
+    }
+
+    /// Get the number of beats that have passed at the specified time.
+    /// The fractional part is the phase within the current beat.
+    pub fn beat_position(&self, seconds: f32) -> f32 {
+        (seconds - self.offset) * self.bpm / 60.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::onset::tests::click_track;
+
+    const SECONDS: f32 = 20.0;
+
+    /// Clicks at the specified tempo, starting at `offset` seconds.
+    fn clicks(bpm: f32, offset: f32) -> Vec<f32> {
+        let period = 60.0 / bpm;
+        let times = (0..)
+            .map(|n| offset + (n as f32) * period)
+            .take_while(|time| *time < SECONDS - 0.1)
+            .collect::<Vec<_>>();
+        click_track(&times, SECONDS, 1.0)
+    }
+
+    fn estimate(samples: Vec<f32>) -> Tempo {
+        let envelope = Envelope::from_samples(samples.into_iter(), 1, 44100);
+        let onsets = Onsets::detect(&envelope);
+        Tempo::estimate(&envelope, &onsets)
+    }
+
+    #[test]
+    fn click_tracks_have_their_tempo() {
+        for bpm in [90.0, 128.0, 174.0] {
+            let tempo = estimate(clicks(bpm, 0.3));
+            // Also fails if the preference for 120 BPM chose half or double time.
+            assert!((tempo.bpm() - bpm).abs() < 1.0, "estimated {} BPM for {} BPM", tempo.bpm(), bpm);
+        }
+    }
+
+    #[test]
+    fn beats_are_on_the_clicks() {
+        let tempo = estimate(clicks(128.0, 0.3));
+        let period = 60.0 / 128.0;
+        for n in [1.0, 10.0, 30.0] {
+            let click = 0.3 + n * period;
+            let position = tempo.beat_position(click);
+            assert!((position - position.round()).abs() < 0.05, "beat position {} at click {}", position, n);
+            // Each click starts a new beat.
+            let next = tempo.beat_position(click + period);
+            assert!((next - position - 1.0).abs() < 0.05);
+            let half = tempo.beat_position(click + 0.5 * period).rem_euclid(1.0);
+            assert!((half - 0.5).abs() < 0.05, "beat phase {} half way between clicks", half);
+        }
+    }
+
+    #[test]
+    fn silence_has_no_tempo() {
+        let tempo = estimate(vec![0.0; 44100 * 5]);
+        assert_eq!(tempo.bpm(), 0.0);
+        assert_eq!(tempo.beat_position(3.0), 0.0);
+    }
+}
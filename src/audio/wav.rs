@@ -0,0 +1,68 @@
+use std::io::{Write, Result, Error, ErrorKind};
+
+use super::SampleStore;
+
+/// Write audio as a 16-bit PCM WAV file.
+/// The audio is cut or padded with silence to exactly `frame_count` frames.
+/// Fails without writing anything if the audio is too long for the sizes in the header.
+pub fn write_wav(store: &SampleStore, frame_count: usize, mut writer: impl Write) -> Result<()> {
+    let channels = store.channels() as usize;
+    let bytes_per_frame = channels * 2;
+    let data_size = frame_count.checked_mul(bytes_per_frame)
+        .and_then(|size| u32::try_from(size).ok())
+        .filter(|size| size.checked_add(36).is_some())
+        .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "audio is too long for a WAV file, which can hold up to 4 GiB"))?;
+
+    // RIFF header
+    writer.write_all(b"RIFF")?;
+    writer.write_all(&(36 + data_size).to_le_bytes())?;
+    writer.write_all(b"WAVE")?;
+
+    // Format chunk
+    writer.write_all(b"fmt ")?;
+    writer.write_all(&16_u32.to_le_bytes())?;
+    writer.write_all(&1_u16.to_le_bytes())?; // PCM
+    writer.write_all(&(channels as u16).to_le_bytes())?;
+    writer.write_all(&store.sample_rate().to_le_bytes())?;
+    writer.write_all(&(store.sample_rate() * (bytes_per_frame as u32)).to_le_bytes())?;
+    writer.write_all(&(bytes_per_frame as u16).to_le_bytes())?;
+    writer.write_all(&16_u16.to_le_bytes())?;
+
+    // Data chunk
+    writer.write_all(b"data")?;
+    writer.write_all(&data_size.to_le_bytes())?;
+    let samples = store.samples()
+        .chain(std::iter::repeat(0.0))
+        .take(frame_count * channels);
+    for sample in samples {
+        let sample = (sample.clamp(-1.0, 1.0) * (i16::MAX as f32)).round() as i16;
+        writer.write_all(&sample.to_le_bytes())?;
+    }
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_sizes() {
+        let store = SampleStore::new(vec![0.5; 8], 2, 44100);
+        let mut data = Vec::new();
+        write_wav(&store, 6, &mut data).unwrap();
+        // 6 stereo frames of 16-bit samples, padded with silence.
+        assert_eq!(data.len(), 44 + 24);
+        assert_eq!(&data[4..8], &(36_u32 + 24).to_le_bytes());
+        assert_eq!(&data[40..44], &24_u32.to_le_bytes());
+    }
+
+    #[test]
+    fn too_long_for_header() {
+        let store = SampleStore::new(Vec::new(), 2, 44100);
+        let mut data = Vec::new();
+        // Over 4 GiB of stereo 16-bit samples.
+        let error = write_wav(&store, 1 << 30, &mut data).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidInput);
+        assert!(data.is_empty());
+    }
+}
@@ -4,6 +4,8 @@ mod audio;
 #[macro_use]
 mod operation;
 mod script;
+mod offline;
+mod watcher;
 
 use winit::event_loop::EventLoop;
 
@@ -12,29 +14,129 @@ use clap::Parser;
 #[derive(Parser)]
 #[command(version, about)]
 struct Args {
+    #[arg(short, long, required_unless_present = "check")]
+    audio: Option<String>,
+    #[arg(short, long, required_unless_present = "check")]
+    script: Option<String>,
+    /// Check scripts for errors, without playing or rendering anything.
+    #[arg(long, value_name = "SCRIPT", num_args = 1.., conflicts_with_all = ["audio", "script"])]
+    check: Vec<String>,
+    /// Delay applied to the visuals to match the audio output, in milliseconds.
+    #[arg(short, long, default_value_t = 0.0, allow_negative_numbers = true)]
+    latency: f32,
+    /// Render to a file or directory instead of a window.
+    /// Use "-" to write a Y4M stream to stdout.
     #[arg(short, long)]
-    audio: String,
-    #[arg(short, long)]
-    script: String
+    output: Option<String>,
+    /// Format of offline renders.
+    #[arg(long, value_enum, default_value_t = OutputFormat::Png)]
+    format: OutputFormat,
+    /// Path to write the audio of a Y4M render to.
+    /// Defaults to the output path with a .wav extension, and must be given when writing to stdout.
+    #[arg(long, required_if_eq_all = [("output", "-"), ("format", "y4m")])]
+    wav: Option<String>,
+    /// Frame rate of offline renders.
+    #[arg(long, default_value_t = 60, value_parser = clap::value_parser!(u32).range(1..))]
+    fps: u32,
+    /// Size of offline renders, as WIDTHxHEIGHT.
+    #[arg(long, default_value = "1080x720", value_parser = parse_size)]
+    size: renderer::Size,
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum OutputFormat {
+    /// A directory of numbered PNG files.
+    Png,
+    /// A YUV4MPEG2 video stream, with the audio written to a separate WAV file.
+    Y4m,
+}
+
+fn parse_size(s: &str) -> Result<renderer::Size, String> {
+    let (width, height) = s.split_once('x').ok_or("expected WIDTHxHEIGHT")?;
+    let width = width.parse::<u32>().map_err(|e| e.to_string())?;
+    let height = height.parse::<u32>().map_err(|e| e.to_string())?;
+    if width == 0 || height == 0 {
+        return Err("size must be non-zero".to_string());
+    }
+    Ok(renderer::Size { width, height })
+}
+
+/// Check each script, and print any errors.
+/// Returns the exit code: 1 if any script has errors.
+fn check_scripts(paths: &[String]) -> i32 {
+    let mut failed = 0;
+    for path in paths {
+        if let Err(report) = script::load_file(path) {
+            eprint!("{}", report);
+            failed += 1;
+        }
+    }
+    if failed > 0 {
+        eprintln!("{} of {} scripts have errors", failed, paths.len());
+        1
+    } else {
+        0
+    }
 }
 
 fn main() {
     let args = Args::parse();
 
-    let audio_source = audio::AudioSource::from_file(&args.audio);
+    if !args.check.is_empty() {
+        std::process::exit(check_scripts(&args.check));
+    }
+    let (Some(audio_path), Some(script_path)) = (args.audio, args.script) else {
+        unreachable!("audio and script are required unless checking");
+    };
 
-    let audio_player = audio::AudioPlayer::new();
-    audio_player.play(&audio_source);
+    let mut audio_source = audio::AudioSource::from_file(&audio_path);
 
     let renderer = renderer::Renderer::new();
 
-    let display = match script::parse_file(&args.script, &renderer) {
+    let mut display = match script::parse_file(&script_path, &renderer) {
         Ok(s) => s,
-        Err(e) => panic!("script error: {:?}", e)
+        Err(report) => {
+            eprint!("{}", report);
+            std::process::exit(1);
+        }
     };
 
+    if let Some(output) = args.output {
+        let offline_renderer = offline::OfflineRenderer::new(args.size, args.fps);
+        match args.format {
+            OutputFormat::Png => {
+                let mut writer = offline::PngSequence::new(output.into()).expect("could not create output directory");
+                offline_renderer.render(&renderer, &mut audio_source, &mut display, &mut writer).expect("could not write frame");
+            },
+            OutputFormat::Y4m => {
+                let wav_path = match (args.wav, output.as_str()) {
+                    (Some(path), _) => std::path::PathBuf::from(path),
+                    (None, "-") => unreachable!("--wav is required when writing video to stdout"),
+                    (None, path) => std::path::Path::new(path).with_extension("wav"),
+                };
+                let wav_file = std::fs::File::create(wav_path).expect("could not create wav file");
+                audio_source.write_wav(std::io::BufWriter::new(wav_file), offline_renderer.duration(&audio_source))
+                    .expect("could not write wav file");
+
+                let video: Box<dyn std::io::Write> = if output == "-" {
+                    Box::new(std::io::stdout().lock())
+                } else {
+                    Box::new(std::fs::File::create(output).expect("could not create video file"))
+                };
+                let mut writer = offline::Y4mWriter::new(std::io::BufWriter::new(video), args.size, args.fps)
+                    .expect("could not write video header");
+                offline_renderer.render(&renderer, &mut audio_source, &mut display, &mut writer).expect("could not write frame");
+            }
+        }
+        return;
+    }
+
+    let audio_player = audio::AudioPlayer::new();
+    let clock = audio_player.play(&audio_source)
+        .with_latency(args.latency / 1000.0);
+
     let event_loop = EventLoop::new().expect("Failed to create event loop");
-    let mut app = app::App::new(renderer, audio_source, display);
+    let mut app = app::App::new(renderer, audio_source, display, clock, script_path);
     event_loop.set_control_flow(winit::event_loop::ControlFlow::Poll);
     event_loop.run_app(&mut app).unwrap();
 }
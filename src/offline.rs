@@ -0,0 +1,268 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf
+};
+
+use crate::{
+    audio::AudioSource,
+    renderer::{Renderer, Display, Size}
+};
+
+/// Receives the frames of an offline render.
+pub trait FrameWriter {
+    /// Write a single frame, given as tightly packed RGBA bytes.
+    fn write_frame(&mut self, index: usize, size: Size, data: &[u8]) -> std::io::Result<()>;
+
+    /// Called after the last frame has been written.
+    fn finish(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Renders an entire track without a window, at a fixed frame rate.
+pub struct OfflineRenderer {
+    size: Size,
+    fps: u32,
+}
+
+impl OfflineRenderer {
+    pub fn new(size: Size, fps: u32) -> Self {
+        Self {
+            size,
+            fps
+        }
+    }
+
+    /// Get the number of frames needed to cover the whole track.
+    pub fn frame_count(&self, audio_source: &AudioSource) -> usize {
+        // Integer arithmetic, so that a track that is a whole number of frames long doesn't get an extra frame.
+        ((audio_source.length() as u64) * (self.fps as u64)).div_ceil(audio_source.sample_rate() as u64) as usize
+    }
+
+    /// Get the length of the rendered video, in seconds.
+    /// This is the length of the track rounded up to a whole frame.
+    pub fn duration(&self, audio_source: &AudioSource) -> f64 {
+        (self.frame_count(audio_source) as f64) / (self.fps as f64)
+    }
+
+    /// Get the time of a frame, in seconds.
+    pub fn frame_time(&self, index: usize) -> f32 {
+        ((index as f64) / (self.fps as f64)) as f32
+    }
+
+    /// Render every frame of the track, and pass them to the writer.
+    pub fn render(&self, renderer: &Renderer, audio_source: &mut AudioSource, display: &mut Display, writer: &mut impl FrameWriter) -> std::io::Result<()> {
+        let frame_count = self.frame_count(audio_source);
+        for index in 0..frame_count {
+            let audio_packet = audio_source.get_frame_data(self.frame_time(index), display.per_channel());
+            let data = display.render_offscreen(renderer, &audio_packet, self.size);
+            writer.write_frame(index, self.size, &data)?;
+
+            if (index + 1) % (self.fps as usize) == 0 || index + 1 == frame_count {
+                eprint!("\rrendered {}/{} frames", index + 1, frame_count);
+            }
+        }
+        eprintln!();
+        writer.finish()
+    }
+}
+
+/// Writes each frame to a numbered PNG file in a directory.
+pub struct PngSequence {
+    dir: PathBuf
+}
+
+impl PngSequence {
+    pub fn new(dir: PathBuf) -> std::io::Result<Self> {
+        std::fs::create_dir_all(&dir)?;
+        Ok(Self {
+            dir
+        })
+    }
+}
+
+impl FrameWriter for PngSequence {
+    fn write_frame(&mut self, index: usize, size: Size, data: &[u8]) -> std::io::Result<()> {
+        let file = File::create(self.dir.join(format!("frame_{:06}.png", index)))?;
+        let mut encoder = png::Encoder::new(BufWriter::new(file), size.width, size.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_source_srgb(png::SrgbRenderingIntent::Perceptual);
+        let mut writer = encoder.write_header().map_err(std::io::Error::other)?;
+        writer.write_image_data(data).map_err(std::io::Error::other)?;
+        writer.finish().map_err(std::io::Error::other)
+    }
+}
+
+/// Writes frames to a YUV4MPEG2 stream, with 4:2:0 chroma subsampling.
+pub struct Y4mWriter<W: Write> {
+    writer: W,
+    y: Vec<u8>,
+    u: Vec<u8>,
+    v: Vec<u8>,
+}
+
+impl<W: Write> Y4mWriter<W> {
+    /// Create a new stream and write the header.
+    pub fn new(mut writer: W, size: Size, fps: u32) -> std::io::Result<Self> {
+        writeln!(writer, "YUV4MPEG2 W{} H{} F{}:1 Ip A1:1 C420jpeg", size.width, size.height, fps)?;
+        Ok(Self {
+            writer,
+            y: Vec::new(),
+            u: Vec::new(),
+            v: Vec::new()
+        })
+    }
+}
+
+impl<W: Write> FrameWriter for Y4mWriter<W> {
+    fn write_frame(&mut self, _index: usize, size: Size, data: &[u8]) -> std::io::Result<()> {
+        let width = size.width as usize;
+        let height = size.height as usize;
+        let chroma_width = width.div_ceil(2);
+        let chroma_height = height.div_ceil(2);
+
+        // BT.601, limited range.
+        self.y.clear();
+        self.y.extend(data.chunks_exact(4).map(|p| {
+            let (r, g, b) = (p[0] as f32, p[1] as f32, p[2] as f32);
+            (16.0 + 0.257 * r + 0.504 * g + 0.098 * b).round() as u8
+        }));
+
+        self.u.clear();
+        self.v.clear();
+        for chroma_y in 0..chroma_height {
+            for chroma_x in 0..chroma_width {
+                // Average each 2x2 block of pixels, clamped to the edge of the image.
+                let (mut r, mut g, mut b) = (0.0, 0.0, 0.0);
+                for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                    let x = (chroma_x * 2 + dx).min(width - 1);
+                    let y = (chroma_y * 2 + dy).min(height - 1);
+                    let p = &data[((y * width + x) * 4)..];
+                    r += p[0] as f32 * 0.25;
+                    g += p[1] as f32 * 0.25;
+                    b += p[2] as f32 * 0.25;
+                }
+                self.u.push((128.0 - 0.148 * r - 0.291 * g + 0.439 * b).round() as u8);
+                self.v.push((128.0 + 0.439 * r - 0.368 * g - 0.071 * b).round() as u8);
+            }
+        }
+
+        self.writer.write_all(b"FRAME\n")?;
+        self.writer.write_all(&self.y)?;
+        self.writer.write_all(&self.u)?;
+        self.writer.write_all(&self.v)
+    }
+
+    fn finish(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::SampleStore;
+
+    const SIZE: Size = Size {
+        width: 4,
+        height: 2
+    };
+
+    /// Silent stereo audio of the specified number of frames.
+    fn silence(frame_count: usize, sample_rate: u32) -> AudioSource {
+        AudioSource::from_store(SampleStore::new(vec![0.0; frame_count * 2], 2, sample_rate))
+    }
+
+    #[test]
+    fn frame_times_are_multiples_of_the_frame_period() {
+        let renderer = OfflineRenderer::new(SIZE, 30);
+        for index in [0, 1, 29, 30, 31, 1000, 108000] {
+            assert_eq!(renderer.frame_time(index), ((index as f64) / 30.0) as f32);
+        }
+        assert_eq!(renderer.frame_time(30), 1.0);
+        assert_eq!(OfflineRenderer::new(SIZE, 24).frame_time(12), 0.5);
+    }
+
+    #[test]
+    fn frame_count_covers_the_track() {
+        let renderer = OfflineRenderer::new(SIZE, 30);
+        // 1470 samples per frame at 44.1 kHz.
+        for frames in [1, 30, 31, 62, 124, 250, 1000] {
+            assert_eq!(renderer.frame_count(&silence(1470 * frames, 44100)), frames, "{} frames", frames);
+            assert_eq!(renderer.frame_count(&silence(1470 * frames - 1, 44100)), frames);
+            assert_eq!(renderer.frame_count(&silence(1470 * frames + 1, 44100)), frames + 1);
+        }
+        assert_eq!(renderer.frame_count(&silence(0, 44100)), 0);
+    }
+
+    #[test]
+    fn duration_is_rounded_up_to_a_frame() {
+        let renderer = OfflineRenderer::new(SIZE, 30);
+        assert_eq!(renderer.duration(&silence(44100, 44100)), 1.0);
+        assert_eq!(renderer.duration(&silence(44101, 44100)), 31.0 / 30.0);
+    }
+
+    /// Render frames of a single colour to a Y4M stream.
+    fn y4m(size: Size, colour: [u8; 4], frames: usize) -> Vec<u8> {
+        let mut writer = Y4mWriter::new(Vec::new(), size, 30).unwrap();
+        let data = colour.repeat((size.width * size.height) as usize);
+        for index in 0..frames {
+            writer.write_frame(index, size, &data).unwrap();
+        }
+        writer.finish().unwrap();
+        writer.writer
+    }
+
+    #[test]
+    fn y4m_header_and_frame_sizes() {
+        let stream = y4m(SIZE, [0, 0, 0, 255], 2);
+        let header = b"YUV4MPEG2 W4 H2 F30:1 Ip A1:1 C420jpeg\n";
+        assert_eq!(&stream[..header.len()], header);
+        // 4x2 luma, and 2x1 for each chroma plane.
+        let frame_size = b"FRAME\n".len() + 4 * 2 * 3 / 2;
+        assert_eq!(stream.len(), header.len() + 2 * frame_size);
+        assert_eq!(&stream[header.len()..][..6], b"FRAME\n");
+        assert_eq!(&stream[header.len() + frame_size..][..6], b"FRAME\n");
+    }
+
+    #[test]
+    fn y4m_odd_sizes_round_chroma_up() {
+        let size = Size {
+            width: 3,
+            height: 3
+        };
+        let stream = y4m(size, [0, 0, 0, 255], 1);
+        let header_size = b"YUV4MPEG2 W3 H3 F30:1 Ip A1:1 C420jpeg\n".len();
+        assert_eq!(stream.len(), header_size + b"FRAME\n".len() + 3 * 3 + 2 * 2 * 2);
+    }
+
+    #[test]
+    fn y4m_uses_limited_range() {
+        let frame = |colour| {
+            let stream = y4m(SIZE, colour, 1);
+            let start = stream.len() - 4 * 2 * 3 / 2;
+            stream[start..].to_vec()
+        };
+        // Luma for each pixel, then one U and one V value for each 2x2 block.
+        assert_eq!(frame([255, 255, 255, 255]), [235, 235, 235, 235, 235, 235, 235, 235, 128, 128, 128, 128]);
+        assert_eq!(frame([0, 0, 0, 255]), [16, 16, 16, 16, 16, 16, 16, 16, 128, 128, 128, 128]);
+    }
+
+    #[test]
+    fn wav_has_the_length_of_the_video() {
+        for (frames, fps) in [(1470 * 31, 30), (1470 * 31 + 5, 30), (44100 * 2 + 1, 24), (1000, 60)] {
+            let audio_source = silence(frames, 44100);
+            let renderer = OfflineRenderer::new(SIZE, fps);
+            let mut wav = Vec::new();
+            audio_source.write_wav(&mut wav, renderer.duration(&audio_source)).unwrap();
+
+            // The data size is in the last 4 bytes of the 44 byte header, and there are 4 bytes per stereo frame.
+            let wav_frames = (u32::from_le_bytes(wav[40..44].try_into().unwrap()) / 4) as usize;
+            // Within half a sample of the video, compared in units of 1 / (44100 * fps) seconds.
+            let video_length = renderer.frame_count(&audio_source) * 44100;
+            assert!((wav_frames * fps as usize).abs_diff(video_length) * 2 <= fps as usize, "{} frames at {} fps", frames, fps);
+        }
+    }
+}
@@ -0,0 +1,173 @@
+/// Built-in functions that depend on the values of previous frames.
+#[derive(Clone, Copy, Debug, strum::EnumString, strum::Display)]
+#[strum(serialize_all = "lowercase")]
+pub enum FilterKind {
+    /// smooth(x, seconds): follow x, taking about `seconds` to catch up.
+    Smooth,
+    /// env(x, attack, release): follow x, rising and falling at different speeds.
+    Env,
+    /// slew(x, rate): follow x, changing by at most `rate` per second.
+    Slew,
+    /// hold(x, seconds): hold the highest value of x for `seconds`.
+    Hold,
+    /// prev(x): the value of x on the previous frame.
+    Prev,
+}
+
+impl FilterKind {
+    /// Get the number of arguments that the filter takes.
+    pub fn arity(self) -> usize {
+        use FilterKind::*;
+        match self {
+            Prev => 1,
+            Smooth | Slew | Hold => 2,
+            Env => 3,
+        }
+    }
+}
+
+/// A filter, with its state from previous frames.
+pub struct Filter {
+    kind: FilterKind,
+    /// The output of the previous frame, or None on the first frame.
+    value: Option<f32>,
+    /// Time since the held value was set, in seconds.
+    hold_time: f32,
+}
+
+impl Filter {
+    pub fn new(kind: FilterKind) -> Self {
+        Self {
+            kind,
+            value: None,
+            hold_time: 0.0
+        }
+    }
+
+    /// Apply the filter for a frame that is `delta_time` seconds after the previous one.
+    /// The number of arguments must match the arity.
+    pub fn apply(&mut self, args: &[f32], delta_time: f32) -> f32 {
+        use FilterKind::*;
+        let input = args[0];
+        let Some(value) = self.value else {
+            self.value = Some(input);
+            return input;
+        };
+
+        let output = match self.kind {
+            Smooth => follow(value, input, args[1], delta_time),
+            Env => {
+                let time = if input > value { args[1] } else { args[2] };
+                follow(value, input, time, delta_time)
+            },
+            Slew => {
+                let max_change = args[1].abs() * delta_time;
+                value + (input - value).clamp(-max_change, max_change)
+            },
+            Hold => {
+                self.hold_time += delta_time;
+                if input >= value || self.hold_time > args[1] {
+                    self.hold_time = 0.0;
+                    input
+                } else {
+                    value
+                }
+            },
+            Prev => {
+                // Store the input, but output the stored value from last frame.
+                self.value = Some(input);
+                return value;
+            },
+        };
+        self.value = Some(output);
+        output
+    }
+}
+
+/// Move exponentially from `value` towards `target`, with time constant `time`.
+fn follow(value: f32, target: f32, time: f32, delta_time: f32) -> f32 {
+    if time <= 0.0 {
+        return target;
+    }
+    let amount = 1.0 - (-delta_time / time).exp();
+    value + (target - value) * amount
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Run a filter over inputs with the given frame times, and return the last output.
+    fn run(kind: FilterKind, frames: &[(&[f32], f32)]) -> f32 {
+        let mut filter = Filter::new(kind);
+        frames.iter().fold(0.0, |_, &(args, delta_time)| filter.apply(args, delta_time))
+    }
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-5, "{a} != {b}");
+    }
+
+    #[test]
+    fn first_frame_passes_input_through() {
+        for kind in [FilterKind::Smooth, FilterKind::Env, FilterKind::Slew, FilterKind::Hold, FilterKind::Prev] {
+            let args = [3.0, 1.0, 1.0];
+            assert_eq!(Filter::new(kind).apply(&args[..kind.arity()], 0.5), 3.0, "{kind}");
+        }
+    }
+
+    #[test]
+    fn smooth_is_independent_of_frame_rate() {
+        let one = run(FilterKind::Smooth, &[(&[0.0, 0.5], 0.0), (&[1.0, 0.5], 0.2)]);
+        let two = run(FilterKind::Smooth, &[(&[0.0, 0.5], 0.0), (&[1.0, 0.5], 0.1), (&[1.0, 0.5], 0.1)]);
+        let uneven = run(FilterKind::Smooth, &[(&[0.0, 0.5], 0.0), (&[1.0, 0.5], 0.05), (&[1.0, 0.5], 0.15)]);
+        assert_close(one, 1.0 - (-0.4f32).exp());
+        assert_close(one, two);
+        assert_close(one, uneven);
+    }
+
+    #[test]
+    fn smooth_with_no_time_jumps_to_input() {
+        assert_eq!(run(FilterKind::Smooth, &[(&[0.0, 0.0], 0.0), (&[1.0, 0.0], 0.01)]), 1.0);
+        assert_eq!(run(FilterKind::Smooth, &[(&[0.0, 1.0], 0.0), (&[1.0, 1.0], 0.0)]), 0.0);
+    }
+
+    #[test]
+    fn env_uses_attack_rising_and_release_falling() {
+        let rise = run(FilterKind::Env, &[(&[0.0, 0.1, 1.0], 0.0), (&[1.0, 0.1, 1.0], 0.1)]);
+        let fall = run(FilterKind::Env, &[(&[1.0, 0.1, 1.0], 0.0), (&[0.0, 0.1, 1.0], 0.1)]);
+        assert_close(rise, 1.0 - (-1.0f32).exp());
+        assert_close(fall, (-0.1f32).exp());
+    }
+
+    #[test]
+    fn slew_limits_change_per_second() {
+        let short = run(FilterKind::Slew, &[(&[0.0, 2.0], 0.0), (&[1.0, 2.0], 0.1)]);
+        let long = run(FilterKind::Slew, &[(&[0.0, 2.0], 0.0), (&[1.0, 2.0], 0.6)]);
+        let down = run(FilterKind::Slew, &[(&[0.0, -2.0], 0.0), (&[-1.0, -2.0], 0.25)]);
+        assert_close(short, 0.2);
+        assert_eq!(long, 1.0);
+        assert_close(down, -0.5);
+    }
+
+    #[test]
+    fn hold_lasts_for_seconds_at_any_frame_rate() {
+        let frames = |delta_time: f32, count: usize| {
+            let mut frames: Vec<(&[f32], f32)> = vec![(&[1.0, 0.5], 0.0)];
+            frames.extend(std::iter::repeat_n((&[0.0, 0.5][..], delta_time), count));
+            run(FilterKind::Hold, &frames)
+        };
+        assert_eq!(frames(0.1, 4), 1.0);
+        assert_eq!(frames(0.1, 6), 0.0);
+        assert_eq!(frames(0.25, 2), 1.0);
+        assert_eq!(frames(0.25, 3), 0.0);
+        assert_eq!(frames(1.0, 1), 0.0);
+    }
+
+    #[test]
+    fn prev_outputs_previous_input() {
+        let mut filter = Filter::new(FilterKind::Prev);
+        assert_eq!(filter.apply(&[1.0], 0.1), 1.0);
+        assert_eq!(filter.apply(&[2.0], 0.5), 1.0);
+        assert_eq!(filter.apply(&[3.0], 0.0), 2.0);
+    }
+}
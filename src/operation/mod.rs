@@ -0,0 +1,283 @@
+mod filter;
+mod variable;
+mod text;
+
+use std::rc::Rc;
+
+use crate::audio::{AudioPacket, AudioParam, Channel};
+
+pub use filter::{Filter, FilterKind};
+pub use variable::{Variable, Binding};
+pub use text::{TextTemplate, TextPart, MAX_PRECISION};
+
+pub enum Operation {
+    Const(f32),
+    Param(AudioParam, Channel),
+    Var(Rc<Variable>),
+    Add(Box<Operation>, Box<Operation>),
+    Sub(Box<Operation>, Box<Operation>),
+    Mul(Box<Operation>, Box<Operation>),
+    Div(Box<Operation>, Box<Operation>),
+    Mod(Box<Operation>, Box<Operation>),
+    Neg(Box<Operation>),
+    Lt(Box<Operation>, Box<Operation>),
+    Le(Box<Operation>, Box<Operation>),
+    Gt(Box<Operation>, Box<Operation>),
+    Ge(Box<Operation>, Box<Operation>),
+    Eq(Box<Operation>, Box<Operation>),
+    Ne(Box<Operation>, Box<Operation>),
+    And(Box<Operation>, Box<Operation>),
+    Or(Box<Operation>, Box<Operation>),
+    If(Box<Operation>, Box<Operation>, Box<Operation>),
+    Call(Function, Vec<Operation>),
+    Filter(Filter, Vec<Operation>)
+}
+
+impl Operation {
+    pub fn eval(&mut self, audio_packet: &AudioPacket) -> f32 {
+        use Operation::*;
+        match self {
+            Const(n) => *n,
+            Param(p, c) => audio_packet.get_param(*p, *c),
+            Var(v) => v.get(),
+            Add(a, b) => a.eval(audio_packet) + b.eval(audio_packet),
+            Sub(a, b) => a.eval(audio_packet) - b.eval(audio_packet),
+            Mul(a, b) => a.eval(audio_packet) * b.eval(audio_packet),
+            Div(a, b) => a.eval(audio_packet) / b.eval(audio_packet),
+            Mod(a, b) => a.eval(audio_packet).rem_euclid(b.eval(audio_packet)),
+            Neg(a) => -a.eval(audio_packet),
+            Lt(a, b) => from_bool(a.eval(audio_packet) < b.eval(audio_packet)),
+            Le(a, b) => from_bool(a.eval(audio_packet) <= b.eval(audio_packet)),
+            Gt(a, b) => from_bool(a.eval(audio_packet) > b.eval(audio_packet)),
+            Ge(a, b) => from_bool(a.eval(audio_packet) >= b.eval(audio_packet)),
+            Eq(a, b) => from_bool(a.eval(audio_packet) == b.eval(audio_packet)),
+            Ne(a, b) => from_bool(a.eval(audio_packet) != b.eval(audio_packet)),
+            And(a, b) => from_bool(to_bool(a.eval(audio_packet)) && to_bool(b.eval(audio_packet))),
+            Or(a, b) => from_bool(to_bool(a.eval(audio_packet)) || to_bool(b.eval(audio_packet))),
+            If(cond, a, b) => if to_bool(cond.eval(audio_packet)) {
+                a.eval(audio_packet)
+            } else {
+                b.eval(audio_packet)
+            },
+            Call(f, args) => {
+                let mut values = [0.0; MAX_ARGUMENTS];
+                for (value, arg) in values.iter_mut().zip(args.iter_mut()) {
+                    *value = arg.eval(audio_packet);
+                }
+                f.apply(&values[..args.len()])
+            },
+            Filter(f, args) => {
+                let mut values = [0.0; MAX_ARGUMENTS];
+                for (value, arg) in values.iter_mut().zip(args.iter_mut()) {
+                    *value = arg.eval(audio_packet);
+                }
+                f.apply(&values[..args.len()], audio_packet.delta_time())
+            },
+        }
+    }
+
+    /// Get the value of the operation, if it is the same on every frame.
+    pub fn constant_value(&self) -> Option<f32> {
+        use Operation::*;
+        let binary = |a: &Operation, b: &Operation, f: fn(f32, f32) -> f32| Some(f(a.constant_value()?, b.constant_value()?));
+        match self {
+            Const(n) => Some(*n),
+            Param(..) | Var(_) | Filter(..) => None,
+            Add(a, b) => binary(a, b, |a, b| a + b),
+            Sub(a, b) => binary(a, b, |a, b| a - b),
+            Mul(a, b) => binary(a, b, |a, b| a * b),
+            Div(a, b) => binary(a, b, |a, b| a / b),
+            Mod(a, b) => binary(a, b, f32::rem_euclid),
+            Neg(a) => Some(-a.constant_value()?),
+            Lt(a, b) => binary(a, b, |a, b| from_bool(a < b)),
+            Le(a, b) => binary(a, b, |a, b| from_bool(a <= b)),
+            Gt(a, b) => binary(a, b, |a, b| from_bool(a > b)),
+            Ge(a, b) => binary(a, b, |a, b| from_bool(a >= b)),
+            Eq(a, b) => binary(a, b, |a, b| from_bool(a == b)),
+            Ne(a, b) => binary(a, b, |a, b| from_bool(a != b)),
+            And(a, b) => binary(a, b, |a, b| from_bool(to_bool(a) && to_bool(b))),
+            Or(a, b) => binary(a, b, |a, b| from_bool(to_bool(a) || to_bool(b))),
+            If(cond, a, b) => if to_bool(cond.constant_value()?) {
+                a.constant_value()
+            } else {
+                b.constant_value()
+            },
+            Call(f, args) => {
+                let mut values = [0.0; MAX_ARGUMENTS];
+                for (value, arg) in values.iter_mut().zip(args.iter()) {
+                    *value = arg.constant_value()?;
+                }
+                Some(f.apply(&values[..args.len()]))
+            },
+        }
+    }
+
+    /// Call `f` for every variable that this operation reads.
+    pub fn visit_variables(&self, f: &mut impl FnMut(&Rc<Variable>)) {
+        use Operation::*;
+        match self {
+            Const(_) | Param(..) => {},
+            Var(v) => f(v),
+            Neg(a) => a.visit_variables(f),
+            Add(a, b) | Sub(a, b) | Mul(a, b) | Div(a, b) | Mod(a, b) |
+            Lt(a, b) | Le(a, b) | Gt(a, b) | Ge(a, b) | Eq(a, b) | Ne(a, b) |
+            And(a, b) | Or(a, b) => {
+                a.visit_variables(f);
+                b.visit_variables(f);
+            },
+            If(cond, a, b) => {
+                cond.visit_variables(f);
+                a.visit_variables(f);
+                b.visit_variables(f);
+            },
+            Call(_, args) | Filter(_, args) => for arg in args {
+                arg.visit_variables(f);
+            },
+        }
+    }
+}
+
+/// Conditions are true if they are non-zero.
+fn to_bool(n: f32) -> bool {
+    n != 0.0
+}
+
+fn from_bool(b: bool) -> f32 {
+    if b { 1.0 } else { 0.0 }
+}
+
+/// The most arguments that any function takes.
+const MAX_ARGUMENTS: usize = 3;
+
+/// Built-in maths functions.
+#[derive(Clone, Copy, Debug, strum::EnumString, strum::Display)]
+#[strum(serialize_all = "lowercase")]
+pub enum Function {
+    Sin,
+    Cos,
+    Tan,
+    Abs,
+    Sqrt,
+    Exp,
+    Log,
+    Floor,
+    Ceil,
+    Round,
+    Fract,
+    Min,
+    Max,
+    Pow,
+    Step,
+    Clamp,
+    #[strum(serialize = "mix", serialize = "lerp")]
+    Mix,
+    Smoothstep,
+}
+
+impl Function {
+    /// Get the number of arguments that the function takes.
+    pub fn arity(self) -> usize {
+        use Function::*;
+        match self {
+            Sin | Cos | Tan | Abs | Sqrt | Exp | Log | Floor | Ceil | Round | Fract => 1,
+            Min | Max | Pow | Step => 2,
+            Clamp | Mix | Smoothstep => 3,
+        }
+    }
+
+    /// Apply the function. The number of arguments must match the arity.
+    fn apply(self, args: &[f32]) -> f32 {
+        use Function::*;
+        match self {
+            Sin => args[0].sin(),
+            Cos => args[0].cos(),
+            Tan => args[0].tan(),
+            Abs => args[0].abs(),
+            Sqrt => args[0].sqrt(),
+            Exp => args[0].exp(),
+            Log => args[0].ln(),
+            Floor => args[0].floor(),
+            Ceil => args[0].ceil(),
+            Round => args[0].round(),
+            Fract => args[0] - args[0].floor(),
+            Min => args[0].min(args[1]),
+            Max => args[0].max(args[1]),
+            Pow => args[0].powf(args[1]),
+            // step(edge, x)
+            Step => if args[1] < args[0] { 0.0 } else { 1.0 },
+            // clamp(x, lo, hi)
+            Clamp => args[0].max(args[1]).min(args[2]),
+            // mix(a, b, t)
+            Mix => args[0] + (args[1] - args[0]) * args[2],
+            // smoothstep(edge0, edge1, x)
+            // Equal edges would divide by zero, so they make a step instead.
+            Smoothstep if args[0] == args[1] => if args[2] < args[0] { 0.0 } else { 1.0 },
+            Smoothstep => {
+                let t = ((args[2] - args[0]) / (args[1] - args[0])).clamp(0.0, 1.0);
+                t * t * (3.0 - 2.0 * t)
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(f: Function, args: &[f32]) -> f32 {
+        let args = args.iter().map(|&n| Operation::Const(n)).collect();
+        Operation::Call(f, args).constant_value().unwrap()
+    }
+
+    #[test]
+    fn functions_take_their_arity() {
+        use Function::*;
+        let cases: &[(Function, &[f32], f32)] = &[
+            (Abs, &[-2.0], 2.0),
+            (Sqrt, &[9.0], 3.0),
+            (Floor, &[1.5], 1.0),
+            (Ceil, &[1.5], 2.0),
+            (Round, &[1.4], 1.0),
+            (Fract, &[-0.25], 0.75),
+            (Min, &[1.0, 2.0], 1.0),
+            (Max, &[1.0, 2.0], 2.0),
+            (Pow, &[2.0, 3.0], 8.0),
+            (Step, &[0.5, 0.4], 0.0),
+            (Step, &[0.5, 0.5], 1.0),
+            (Clamp, &[3.0, 0.0, 2.0], 2.0),
+            (Clamp, &[-1.0, 0.0, 2.0], 0.0),
+            (Mix, &[2.0, 4.0, 0.25], 2.5),
+            (Smoothstep, &[0.0, 1.0, 0.5], 0.5),
+            (Smoothstep, &[0.0, 1.0, 2.0], 1.0),
+            (Smoothstep, &[0.5, 0.5, 0.4], 0.0),
+            (Smoothstep, &[0.5, 0.5, 0.5], 1.0),
+            (Smoothstep, &[0.5, 0.5, 0.6], 1.0),
+        ];
+        for &(f, args, expected) in cases {
+            assert_eq!(args.len(), f.arity(), "{f}");
+            assert_eq!(call(f, args), expected, "{f}{args:?}");
+        }
+    }
+
+    #[test]
+    fn modulo_is_euclidean() {
+        let rem = |a, b| Operation::Mod(Box::new(Operation::Const(a)), Box::new(Operation::Const(b))).constant_value().unwrap();
+        assert_eq!(rem(7.0, 3.0), 1.0);
+        assert_eq!(rem(-1.0, 3.0), 2.0);
+        assert_eq!(rem(-3.0, 3.0), 0.0);
+        assert_eq!(rem(7.0, -3.0), 1.0);
+        assert_eq!(rem(-7.0, -3.0), 2.0);
+        assert_eq!(rem(2.5, 1.0), 0.5);
+        assert!(rem(1.0, 0.0).is_nan());
+    }
+
+    #[test]
+    fn arity_is_at_most_max_arguments() {
+        for name in ["sin", "cos", "tan", "exp", "log", "min", "pow", "clamp", "mix", "lerp", "smoothstep"] {
+            let f: Function = name.parse().unwrap();
+            assert!(f.arity() <= MAX_ARGUMENTS, "{name}");
+            let args: Vec<f32> = (1..=f.arity()).map(|i| i as f32 * 0.25).collect();
+            assert!(call(f, &args).is_finite(), "{name}");
+        }
+    }
+}
@@ -0,0 +1,48 @@
+use std::fmt::Write;
+
+use crate::audio::{AudioPacket, MetadataField};
+use super::Operation;
+
+/// The most decimal places that a value can be shown with.
+pub const MAX_PRECISION: usize = 9;
+
+/// A string with values inserted into it, which is recalculated each frame.
+#[derive(Default)]
+pub struct TextTemplate {
+    parts: Vec<TextPart>,
+    text: String
+}
+
+pub enum TextPart {
+    Literal(String),
+    /// A value, and the number of decimal places to show it with.
+    Value(Operation, Option<usize>),
+    Metadata(MetadataField)
+}
+
+impl TextTemplate {
+    pub fn new(parts: Vec<TextPart>) -> Self {
+        Self {
+            parts,
+            text: String::new()
+        }
+    }
+
+    pub fn eval(&mut self, audio_packet: &AudioPacket) -> &str {
+        self.text.clear();
+        for part in &mut self.parts {
+            match part {
+                TextPart::Literal(s) => self.text.push_str(s),
+                TextPart::Value(operation, precision) => {
+                    let value = operation.eval(audio_packet);
+                    let _ = match precision {
+                        Some(precision) => write!(self.text, "{:.*}", *precision, value),
+                        None => write!(self.text, "{}", value)
+                    };
+                },
+                TextPart::Metadata(field) => self.text.push_str(audio_packet.metadata().get(*field)),
+            }
+        }
+        &self.text
+    }
+}
@@ -0,0 +1,59 @@
+use std::{
+    cell::Cell,
+    rc::Rc
+};
+
+use crate::audio::AudioPacket;
+use super::Operation;
+
+/// A value defined with `let`, which is calculated once per frame.
+pub struct Variable {
+    name: String,
+    value: Cell<f32>
+}
+
+impl Variable {
+    pub fn new(name: String) -> Self {
+        Self {
+            name,
+            value: Cell::new(0.0)
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Get the value calculated for the current frame.
+    pub fn get(&self) -> f32 {
+        self.value.get()
+    }
+}
+
+/// A variable, and the operation that calculates it.
+pub struct Binding {
+    variable: Rc<Variable>,
+    operation: Operation
+}
+
+impl Binding {
+    pub fn new(variable: Rc<Variable>, operation: Operation) -> Self {
+        Self {
+            variable,
+            operation
+        }
+    }
+
+    pub fn variable(&self) -> &Rc<Variable> {
+        &self.variable
+    }
+
+    pub fn operation(&self) -> &Operation {
+        &self.operation
+    }
+
+    /// Calculate the value of the variable for this frame.
+    pub fn update(&mut self, audio_packet: &AudioPacket) {
+        self.variable.value.set(self.operation.eval(audio_packet));
+    }
+}
@@ -4,12 +4,13 @@ pub mod scene;
 use winit::window::Window;
 use std::{
     rc::Rc,
-    cell::RefCell,
+    cell::{RefCell, OnceCell},
     collections::HashMap
 };
 use crate::{
-    audio::AudioPacket,
-    operation::Operation
+    audio::{AudioPacket, Channel},
+    operation::{Operation, TextTemplate, Binding},
+    script::Span
 };
 use scene::Scene;
 use object::*;
@@ -22,59 +23,256 @@ pub struct Size {
 
 /// The overall image to render.
 pub struct Display {
-    scene: Box<dyn Scene>
+    /// Variables defined with `let`, sorted so that each comes after the ones it depends on.
+    bindings: Vec<Binding>,
+    scene: Box<dyn Scene>,
+    /// Whether the script reads a single channel of the audio.
+    per_channel: bool
 }
 
 impl Display {
-    pub fn new(scene: Box<dyn Scene>) -> Self {
+    pub fn new(bindings: Vec<Binding>, scene: Box<dyn Scene>, per_channel: bool) -> Self {
         Self {
-            scene
+            bindings,
+            scene,
+            per_channel
         }
     }
 
+    /// Whether the left and right channels need to be analysed separately for this display.
+    pub fn per_channel(&self) -> bool {
+        self.per_channel
+    }
+
+    fn update(&mut self, renderer: &Renderer, audio_packet: &AudioPacket, size: Size) {
+        for binding in &mut self.bindings {
+            binding.update(audio_packet);
+        }
+        self.scene.update(renderer, audio_packet, size);
+    }
+
     pub fn render(&mut self, renderer: &Renderer, audio_packet: &AudioPacket, surface: &mut Surface) {
         let size = Size { width: surface.surface_config.width, height: surface.surface_config.height };
         let surface_tex = surface.surface.get_current_texture().expect("could not get texture");
 
-        self.scene.set_display(&surface_tex);
-        self.scene.update(renderer, audio_packet, size);
+        self.scene.set_display(&surface_tex.texture);
+        self.update(renderer, audio_packet, size);
         self.scene.draw(renderer);
 
         surface_tex.present();
     }
+
+    /// Render to the scene's own texture instead of a window.
+    /// Returns the image as tightly packed RGBA bytes.
+    pub fn render_offscreen(&mut self, renderer: &Renderer, audio_packet: &AudioPacket, size: Size) -> Vec<u8> {
+        self.update(renderer, audio_packet, size);
+        self.scene.draw(renderer);
+
+        let tex = self.scene.get_tex().expect("scene has no texture");
+        let mut data = renderer.read_texture(tex);
+        // Textures are BGRA.
+        for pixel in data.chunks_exact_mut(4) {
+            pixel.swap(0, 2);
+        }
+        data
+    }
 }
 
-pub struct Mapping(HashMap<RenderParam, Operation>);
+/// The parameters of an object, with the span of each parameter's name in the script.
+#[derive(Default)]
+pub struct Mapping {
+    params: HashMap<RenderParam, (Operation, Span)>,
+    blend: Option<(BlendMode, Span)>,
+    channel: Option<(Channel, Span)>,
+    text: Option<(TextTemplate, Span)>,
+    image: Option<(Rc<object::image::ImageData>, Span)>,
+    points: Option<(Vec<[Operation; 2]>, Span)>,
+    join: Option<(shape::Join, Span)>,
+    cap: Option<(shape::Cap, Span)>,
+    /// Errors found while taking parameters, such as missing parameters.
+    errors: Vec<CreationError>
+}
 
 impl Mapping {
-    pub fn new(from: (RenderParam, Operation)) -> Self {
-        Self(
-            HashMap::from([from])
-        )
+    pub fn add(mut self, param: (RenderParam, Operation, Span)) -> Self {
+        self.params.insert(param.0, (param.1, param.2));
+        self
     }
 
-    pub fn add(mut self, param: (RenderParam, Operation)) -> Self {
-        self.0.insert(param.0, param.1);
-        Self(self.0)
+    pub fn set_blend(mut self, blend: (BlendMode, Span)) -> Self {
+        self.blend = Some(blend);
+        self
     }
 
-    pub fn get(&mut self, param: RenderParam) -> Result<Operation, CreationError> {
-        self.0.remove(&param).ok_or(CreationError::MissingParameter(param))
+    pub fn set_channel(mut self, channel: (Channel, Span)) -> Self {
+        self.channel = Some(channel);
+        self
     }
 
-    pub fn check_extra_parameters(&self) -> Result<(), CreationError> {
-        if let Some((param, _)) = self.0.iter().next() {
-            Err(CreationError::ExtraParameter(*param))
-        } else {
+    pub fn set_text(mut self, text: (TextTemplate, Span)) -> Self {
+        self.text = Some(text);
+        self
+    }
+
+    pub fn set_image(mut self, image: (Rc<object::image::ImageData>, Span)) -> Self {
+        self.image = Some(image);
+        self
+    }
+
+    pub fn set_points(mut self, points: (Vec<[Operation; 2]>, Span)) -> Self {
+        self.points = Some(points);
+        self
+    }
+
+    pub fn set_join(mut self, join: (shape::Join, Span)) -> Self {
+        self.join = Some(join);
+        self
+    }
+
+    pub fn set_cap(mut self, cap: (shape::Cap, Span)) -> Self {
+        self.cap = Some(cap);
+        self
+    }
+
+    /// Mark a parameter as given, when its value had an error that has already been reported.
+    /// This stops it from also being reported as missing.
+    pub fn add_placeholder(self, param: RenderParam, span: Span) -> Self {
+        match param {
+            RenderParam::Blend => self.set_blend((BlendMode::default(), span)),
+            RenderParam::Channel => self.set_channel((Channel::Both, span)),
+            RenderParam::Text => self.set_text((TextTemplate::default(), span)),
+            RenderParam::File => self.set_image((Rc::default(), span)),
+            RenderParam::Points => self.set_points((Vec::new(), span)),
+            RenderParam::Join => self.set_join((shape::Join::default(), span)),
+            RenderParam::Cap => self.set_cap((shape::Cap::default(), span)),
+            param => self.add((param, Operation::Const(0.0), span))
+        }
+    }
+
+    /// Get a required parameter. If it is missing, the error is reported by `check_parameters`,
+    /// and a placeholder is returned so that the rest of the parameters can still be checked.
+    pub fn get(&mut self, param: RenderParam) -> Operation {
+        self.get_optional(param).unwrap_or_else(|| {
+            self.errors.push(CreationError::MissingParameter(param));
+            Operation::Const(0.0)
+        })
+    }
+
+    /// Get an optional parameter, which has no default value.
+    pub fn get_optional(&mut self, param: RenderParam) -> Option<Operation> {
+        self.params.remove(&param).map(|(operation, _)| operation)
+    }
+
+    /// Get the location of a parameter in the script, if it was specified.
+    pub fn span(&self, param: RenderParam) -> Option<Span> {
+        self.params.get(&param).map(|(_, span)| *span)
+    }
+
+    /// Get an optional parameter, which uses a constant value if not specified.
+    pub fn get_or(&mut self, param: RenderParam, default: f32) -> Operation {
+        self.params.remove(&param)
+            .map_or(Operation::Const(default), |(operation, _)| operation)
+    }
+
+    /// Get the blend mode used by the object. Uses alpha blending if not specified.
+    pub fn take_blend(&mut self) -> BlendMode {
+        self.blend.take().map(|(blend, _)| blend).unwrap_or_default()
+    }
+
+    /// Get the audio channel used by the object. Uses all channels if not specified.
+    pub fn take_channel(&mut self) -> Channel {
+        self.channel.take().map_or(Channel::Both, |(channel, _)| channel)
+    }
+
+    /// Like `get`, these report a missing parameter and return a placeholder.
+    pub fn take_text(&mut self) -> TextTemplate {
+        match self.text.take() {
+            Some((text, _)) => text,
+            None => self.missing(RenderParam::Text)
+        }
+    }
+
+    pub fn take_image(&mut self) -> Rc<object::image::ImageData> {
+        match self.image.take() {
+            Some((image, _)) => image,
+            None => self.missing(RenderParam::File)
+        }
+    }
+
+    pub fn take_points(&mut self) -> Vec<[Operation; 2]> {
+        match self.points.take() {
+            Some((points, _)) => points,
+            None => self.missing(RenderParam::Points)
+        }
+    }
+
+    fn missing<T: Default>(&mut self, param: RenderParam) -> T {
+        self.errors.push(CreationError::MissingParameter(param));
+        T::default()
+    }
+
+    /// Get the way that corners of lines are drawn. Uses mitred corners if not specified.
+    pub fn take_join(&mut self) -> shape::Join {
+        self.join.take().map(|(join, _)| join).unwrap_or_default()
+    }
+
+    /// Get the way that the ends of lines are drawn. Uses butt caps if not specified.
+    pub fn take_cap(&mut self) -> shape::Cap {
+        self.cap.take().map(|(cap, _)| cap).unwrap_or_default()
+    }
+
+    /// Report an error in the values of the parameters.
+    pub fn error(&mut self, error: CreationError) {
+        self.errors.push(error);
+    }
+
+    /// Check that no parameters were missing, and that every parameter has been used.
+    /// Returns every error, with unused parameters in the order they appear in the script.
+    pub fn check_parameters(&self) -> Result<(), Vec<CreationError>> {
+        let mut extra = self.params.iter()
+            .map(|(param, (_, span))| (*param, *span))
+            .chain(self.blend.map(|(_, span)| (RenderParam::Blend, span)))
+            .chain(self.channel.map(|(_, span)| (RenderParam::Channel, span)))
+            .chain(self.text.as_ref().map(|(_, span)| (RenderParam::Text, *span)))
+            .chain(self.image.as_ref().map(|(_, span)| (RenderParam::File, *span)))
+            .chain(self.points.as_ref().map(|(_, span)| (RenderParam::Points, *span)))
+            .chain(self.join.map(|(_, span)| (RenderParam::Join, span)))
+            .chain(self.cap.map(|(_, span)| (RenderParam::Cap, span)))
+            .collect::<Vec<_>>();
+        extra.sort_by_key(|(_, span)| span.start);
+        let errors = self.errors.iter().copied()
+            .chain(extra.into_iter().map(|(param, span)| CreationError::ExtraParameter(param, span)))
+            .collect::<Vec<_>>();
+        if errors.is_empty() {
             Ok(())
+        } else {
+            Err(errors)
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub enum CreationError {
     MissingParameter(RenderParam),
-    ExtraParameter(RenderParam)
+    ExtraParameter(RenderParam, Span),
+    /// The parameters at the bottom and top of a range, where the bottom isn't below the top.
+    InvalidRange(RenderParam, RenderParam, Option<Span>)
+}
+
+impl CreationError {
+    /// Whether the error comes from the values of the parameters, rather than which parameters were given.
+    pub fn depends_on_values(&self) -> bool {
+        matches!(self, CreationError::InvalidRange(..))
+    }
+
+    /// Get the location of the error in the script, if it is known.
+    pub fn span(&self) -> Option<Span> {
+        match *self {
+            CreationError::MissingParameter(_) => None,
+            CreationError::ExtraParameter(_, span) => Some(span),
+            CreationError::InvalidRange(_, _, span) => span,
+        }
+    }
 }
 
 impl std::fmt::Display for CreationError {
@@ -82,7 +280,8 @@ impl std::fmt::Display for CreationError {
         use CreationError::*;
         match *self {
             MissingParameter(p) =>  write!(f, "missing required parameter {}", p),
-            ExtraParameter(p) =>    write!(f, "invalid parameter {}", p),
+            ExtraParameter(p, _) => write!(f, "invalid parameter {}", p),
+            InvalidRange(lo, hi, _) => write!(f, "{} must be less than {}", lo, hi),
         }
     }
 }
@@ -94,24 +293,31 @@ pub struct Renderer {
     device: wgpu::Device,
     queue: wgpu::Queue,
 
-    pipelines: Rc<RefCell< HashMap<ObjectType, wgpu::RenderPipeline> >>
+    pipelines: Rc<RefCell< HashMap<(ObjectType, BlendMode), wgpu::RenderPipeline> >>,
+    /// Shared by every pipeline, so that an object's transform stays bound when its pipeline is set.
+    transform_layout: wgpu::BindGroupLayout,
+    font_atlas: OnceCell<Rc<text::FontAtlas>>
 }
 
 impl Renderer {
     pub fn new() -> Self {
         let instance = wgpu::Instance::new(&Default::default());
 
-        let adapter = futures::executor::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
-            power_preference: wgpu::PowerPreference::default(),
-            force_fallback_adapter: false,
-            compatible_surface: None,
-        })).expect("Failed to find appropriate adapter");
+        // Use a software adapter if there is no GPU available.
+        let adapter = [false, true].into_iter()
+            .find_map(|force_fallback_adapter| futures::executor::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::default(),
+                force_fallback_adapter,
+                compatible_surface: None,
+            })).ok())
+            .expect("Failed to find appropriate adapter");
 
         let (device, queue) = futures::executor::block_on(adapter.request_device(&wgpu::DeviceDescriptor {
             ..Default::default()
         })).expect("Failed to create device");
 
         let pipelines = Rc::new(RefCell::new(HashMap::new()));
+        let transform_layout = transform::create_bind_group_layout(&device);
 
         Self {
             instance,
@@ -119,7 +325,9 @@ impl Renderer {
             device,
             queue,
 
-            pipelines
+            pipelines,
+            transform_layout,
+            font_atlas: OnceCell::new()
         }
     }
 
@@ -137,6 +345,50 @@ impl Renderer {
     pub fn resize_surface(&self, surface: &mut Surface, width: u32, height: u32) {
         surface.resize(width, height, &self.device);
     }
+
+    /// Copy the contents of a 4 byte-per-pixel texture back from the GPU.
+    /// Returns tightly packed rows of pixels.
+    pub fn read_texture(&self, tex: &wgpu::Texture) -> Vec<u8> {
+        let width = tex.width();
+        let height = tex.height();
+        let row_size = width * 4;
+        let padded_row_size = row_size.div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT) * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+
+        let buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: (padded_row_size as u64) * (height as u64),
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false
+        });
+
+        let mut command_encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        command_encoder.copy_texture_to_buffer(
+            tex.as_image_copy(),
+            wgpu::TexelCopyBufferInfo {
+                buffer: &buffer,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_row_size),
+                    rows_per_image: Some(height)
+                }
+            },
+            tex.size()
+        );
+        self.queue.submit([command_encoder.finish()]);
+
+        let slice = buffer.slice(..);
+        slice.map_async(wgpu::MapMode::Read, |result| result.expect("could not map buffer"));
+        self.device.poll(wgpu::PollType::Wait).expect("could not read texture");
+
+        let mapped = slice.get_mapped_range();
+        let data = mapped.chunks_exact(padded_row_size as usize)
+            .flat_map(|row| &row[..(row_size as usize)])
+            .copied()
+            .collect();
+        drop(mapped);
+        buffer.unmap();
+        data
+    }
 }
 
 // Render commands
@@ -144,26 +396,37 @@ impl Renderer {
     pub fn new_render_pass<'a>(&'a self, tex_view: wgpu::TextureView) -> RenderPass<'a> {
         let command_encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
         RenderPass {
-            renderer: &self,
+            renderer: self,
             command_encoder,
             tex_view,
             render_pass: None
         }
     }
 
-    pub fn get_render_pipeline(&self, renderable: ObjectType) -> wgpu::RenderPipeline {
+    pub fn get_render_pipeline(&self, renderable: ObjectType, blend: BlendMode) -> wgpu::RenderPipeline {
         let mut pipelines = self.pipelines.borrow_mut();
 
-        if !pipelines.contains_key(&renderable) {
-            let pipeline = match renderable {
-                ObjectType::Circle =>   circle::CircleRenderable::create_pipeline(&self.device),
-                ObjectType::Quad =>     quad::QuadRenderable::create_pipeline(&self.device),
-            };
-            pipelines.insert(renderable, pipeline);
-        }
+        pipelines.entry((renderable, blend))
+            .or_insert_with(|| match renderable {
+                ObjectType::Circle =>   circle::CircleRenderable::create_pipeline(&self.device, &self.transform_layout, blend),
+                ObjectType::Quad =>     quad::QuadRenderable::create_pipeline(&self.device, &self.transform_layout, blend),
+                ObjectType::Waveform => waveform::WaveformRenderable::create_pipeline(&self.device, &self.transform_layout, blend),
+                ObjectType::Spectrum => spectrum::SpectrumRenderable::create_pipeline(&self.device, &self.transform_layout, blend),
+                ObjectType::Text =>     text::TextRenderable::create_pipeline(&self.device, &self.transform_layout, blend),
+                ObjectType::Image =>    image::ImageRenderable::create_pipeline(&self.device, &self.transform_layout, blend),
+                ObjectType::Line | ObjectType::Polygon | ObjectType::Polyline => shape::Shape::create_pipeline(&self.device, &self.transform_layout, blend),
+            })
+            .clone()
+    }
 
-        pipelines.get(&renderable)
-            .expect("could not find pipeline")
+    /// Get the layout of the bind group that holds an object's transform.
+    pub fn get_transform_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.transform_layout
+    }
+
+    /// Get the glyphs used by text objects. They are rasterized the first time this is called.
+    pub fn get_font_atlas(&self) -> Rc<text::FontAtlas> {
+        self.font_atlas.get_or_init(|| Rc::new(text::FontAtlas::new(&self.device, &self.queue)))
             .clone()
     }
 }
@@ -221,6 +484,7 @@ impl Surface {
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, strum::EnumString, strum::Display)]
+#[strum(serialize_all = "snake_case")]
 pub enum RenderParam {
     #[strum(ascii_case_insensitive)]
     X,
@@ -233,11 +497,110 @@ pub enum RenderParam {
     #[strum(ascii_case_insensitive)]
     B,
     #[strum(ascii_case_insensitive)]
+    A,
+    #[strum(ascii_case_insensitive)]
     Radius,
     #[strum(serialize = "line_width")]
     LineWidth,
     #[strum(ascii_case_insensitive)]
     Width,
     #[strum(ascii_case_insensitive)]
-    Height
+    Height,
+    #[strum(ascii_case_insensitive)]
+    Bars,
+    #[strum(ascii_case_insensitive)]
+    Gap,
+    #[strum(serialize = "min_freq")]
+    MinFreq,
+    #[strum(serialize = "max_freq")]
+    MaxFreq,
+    #[strum(ascii_case_insensitive)]
+    Log,
+    #[strum(serialize = "db_floor")]
+    DbFloor,
+    #[strum(serialize = "db_ceiling")]
+    DbCeiling,
+    #[strum(ascii_case_insensitive)]
+    Mirror,
+    /// How the object is combined with the objects behind it. This is set with a blend mode rather than an expression.
+    #[strum(ascii_case_insensitive)]
+    Blend,
+    /// Audio channel to display. This is set with a channel name rather than an expression.
+    #[strum(ascii_case_insensitive)]
+    Channel,
+    /// String to display. This is set with a string rather than an expression.
+    #[strum(ascii_case_insensitive)]
+    Text,
+    /// Image file to display. This is set with a string rather than an expression.
+    #[strum(ascii_case_insensitive)]
+    File,
+    #[strum(ascii_case_insensitive)]
+    Size,
+    #[strum(ascii_case_insensitive)]
+    Align,
+    #[strum(ascii_case_insensitive)]
+    Rotation,
+    #[strum(serialize = "scale_x")]
+    ScaleX,
+    #[strum(serialize = "scale_y")]
+    ScaleY,
+    #[strum(serialize = "anchor_x")]
+    AnchorX,
+    #[strum(serialize = "anchor_y")]
+    AnchorY,
+    #[strum(serialize = "x1")]
+    X1,
+    #[strum(serialize = "y1")]
+    Y1,
+    #[strum(serialize = "x2")]
+    X2,
+    #[strum(serialize = "y2")]
+    Y2,
+    #[strum(ascii_case_insensitive)]
+    Sides,
+    /// Points along a line. This is set with a list of points rather than an expression.
+    #[strum(ascii_case_insensitive)]
+    Points,
+    /// How the corners of lines are drawn. This is set with a name rather than an expression.
+    #[strum(ascii_case_insensitive)]
+    Join,
+    /// How the ends of lines are drawn. This is set with a name rather than an expression.
+    #[strum(ascii_case_insensitive)]
+    Cap
+}
+
+/// How an object is combined with the objects behind it.
+/// Shaders output colours with premultiplied alpha.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default, strum::EnumString, strum::Display)]
+pub enum BlendMode {
+    #[default]
+    #[strum(ascii_case_insensitive)]
+    Alpha,
+    #[strum(ascii_case_insensitive)]
+    Additive,
+    #[strum(ascii_case_insensitive)]
+    Multiply,
+    #[strum(ascii_case_insensitive)]
+    Screen
+}
+
+impl BlendMode {
+    pub fn blend_state(self) -> wgpu::BlendState {
+        use wgpu::{BlendComponent, BlendFactor, BlendOperation};
+        let color = |src_factor, dst_factor| BlendComponent {
+            src_factor,
+            dst_factor,
+            operation: BlendOperation::Add
+        };
+        let color = match self {
+            BlendMode::Alpha =>     color(BlendFactor::One, BlendFactor::OneMinusSrcAlpha),
+            BlendMode::Additive =>  color(BlendFactor::One, BlendFactor::One),
+            BlendMode::Multiply =>  color(BlendFactor::Dst, BlendFactor::OneMinusSrcAlpha),
+            BlendMode::Screen =>    color(BlendFactor::OneMinusDst, BlendFactor::One),
+        };
+        wgpu::BlendState {
+            color,
+            alpha: BlendComponent::OVER
+        }
+    }
 }
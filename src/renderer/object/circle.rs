@@ -3,13 +3,15 @@ use wgpu::util::DeviceExt;
 
 use crate::{
     audio::AudioPacket, operation::Operation, renderer::{
-        Renderer, RenderPass, RenderParam, Mapping, CreationError
+        Renderer, RenderPass, RenderParam, Mapping, BlendMode
     }
 };
-use super::{ObjectRenderable, ObjectType};
+use super::{ObjectRenderable, ObjectType, Bounds, transform};
 
 const CIRCLE_SIZE: usize = 90;
 const VERTEX_COUNT: usize = (CIRCLE_SIZE + 1) * 2;
+/// The number of values in each copy's uniforms.
+const UNIFORM_COUNT: usize = 9;
 
 #[derive(Zeroable, Pod, Clone, Copy)]
 #[repr(C)]
@@ -17,16 +19,19 @@ struct Vertex {
     pos: [f32; 2]
 }
 
-/// An instance of a circle.
+/// An instance of a circle, which can have several copies.
 pub struct CircleRenderable {
-    params: CircleParameters,
+    /// One for each copy.
+    params: Vec<CircleParameters>,
     pipeline: wgpu::RenderPipeline,
     vertex_buffer: wgpu::Buffer,
+    uniform_data: Vec<f32>,
     uniform_buffer: wgpu::Buffer,
     bind_group: wgpu::BindGroup
 }
 
-struct CircleParameters {
+/// The parameters of a circle, taken from its mapping.
+pub struct CircleParameters {
     x: Operation,
     y: Operation,
     radius: Operation,
@@ -34,27 +39,43 @@ struct CircleParameters {
     r: Operation,
     g: Operation,
     b: Operation,
+    a: Operation,
+}
+
+impl CircleParameters {
+    pub fn new(mapping: &mut Mapping) -> Self {
+        Self {
+            x: mapping.get(RenderParam::X),
+            y: mapping.get(RenderParam::Y),
+            radius: mapping.get(RenderParam::Radius),
+            line_width: mapping.get(RenderParam::LineWidth),
+            r: mapping.get(RenderParam::R),
+            g: mapping.get(RenderParam::G),
+            b: mapping.get(RenderParam::B),
+            a: mapping.get_or(RenderParam::A, 1.0),
+        }
+    }
 }
 
 impl CircleRenderable {
-    pub fn create_pipeline(device: &wgpu::Device) -> wgpu::RenderPipeline {
+    pub fn create_pipeline(device: &wgpu::Device, transform_layout: &wgpu::BindGroupLayout, blend: BlendMode) -> wgpu::RenderPipeline {
         let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             label: None,
             entries: &[
                 wgpu::BindGroupLayoutEntry {
                     binding: 0,
                     visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
-                    ty: wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Uniform, has_dynamic_offset: false, min_binding_size: None },
+                    ty: wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Storage { read_only: true }, has_dynamic_offset: false, min_binding_size: None },
                     count: None
                 }
             ]
         });
         let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: None,
-            bind_group_layouts: &[&bind_group_layout],
+            bind_group_layouts: &[&bind_group_layout, transform_layout],
             push_constant_ranges: &[]
         });
-        let shader_module = device.create_shader_module(wgpu::include_wgsl!("shaders/circle.wgsl"));
+        let shader_module = transform::create_shader_module(device, "circle", include_str!("shaders/circle.wgsl"));
         let circle_desc = wgpu::RenderPipelineDescriptor {
             label: Some("circle"),
             layout: Some(&pipeline_layout),
@@ -91,7 +112,7 @@ impl CircleRenderable {
                 entry_point: Some("fs_main"),
                 targets: &[Some(wgpu::ColorTargetState {
                     format: wgpu::TextureFormat::Bgra8UnormSrgb,
-                    blend: None,
+                    blend: Some(blend.blend_state()),
                     write_mask: wgpu::ColorWrites::ALL,
                 })],
                 compilation_options: Default::default()
@@ -102,8 +123,8 @@ impl CircleRenderable {
         device.create_render_pipeline(&circle_desc)
     }
 
-    /// Create a new circle to display on-screen.
-    pub fn new(mut mapping: Mapping, renderer: &Renderer) -> Result<Self, CreationError> {
+    /// Create a new circle to display on-screen, with a copy for each set of parameters.
+    pub fn new(params: Vec<CircleParameters>, blend: BlendMode, renderer: &Renderer) -> Self {
         // TODO: share vertex buffer?
         let mut buf = Vec::new();
         for step in 0..=CIRCLE_SIZE {
@@ -116,15 +137,15 @@ impl CircleRenderable {
         let vertex_buffer = renderer.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: None,
             usage: wgpu::BufferUsages::VERTEX,
-            contents: &bytemuck::cast_slice(&buf)
+            contents: bytemuck::cast_slice(&buf)
         });
         let uniform_buffer = renderer.device.create_buffer(&wgpu::BufferDescriptor {
             label: None,
-            size: (std::mem::size_of::<f32>() as u64) * 8,
-            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            size: (std::mem::size_of::<f32>() * UNIFORM_COUNT * params.len()) as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
             mapped_at_creation: false
         });
-        let pipeline = renderer.get_render_pipeline(ObjectType::Circle);
+        let pipeline = renderer.get_render_pipeline(ObjectType::Circle, blend);
         let bind_group_layout = pipeline.get_bind_group_layout(0);
         let bind_group = renderer.device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: None,
@@ -136,39 +157,44 @@ impl CircleRenderable {
                 }
             ]
         });
-        let params = CircleParameters {
-            x: mapping.get(RenderParam::X)?,
-            y: mapping.get(RenderParam::Y)?,
-            radius: mapping.get(RenderParam::Radius)?,
-            line_width: mapping.get(RenderParam::LineWidth)?,
-            r: mapping.get(RenderParam::R)?,
-            g: mapping.get(RenderParam::G)?,
-            b: mapping.get(RenderParam::B)?,
-        };
-        mapping.check_extra_parameters()?;
-        Ok(Self {
+        Self {
             params,
             pipeline,
             vertex_buffer,
+            uniform_data: Vec::new(),
             uniform_buffer,
             bind_group
-        })
+        }
     }
 }
 
 impl ObjectRenderable for CircleRenderable {
-    fn update(&mut self, renderer: &Renderer, audio_packet: &AudioPacket, aspect_ratio: f32) {
-        let uniform_data = [
-            aspect_ratio,
-            self.params.x.eval(audio_packet),
-            self.params.y.eval(audio_packet),
-            self.params.radius.eval(audio_packet),
-            self.params.line_width.eval(audio_packet),
-            self.params.r.eval(audio_packet),
-            self.params.g.eval(audio_packet),
-            self.params.b.eval(audio_packet)
-        ];
-        renderer.queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&uniform_data));
+    fn update(&mut self, renderer: &Renderer, audio_packet: &AudioPacket, aspect_ratio: f32, bounds: &mut Vec<Bounds>) {
+        self.uniform_data.clear();
+        for params in &mut self.params {
+            let x = params.x.eval(audio_packet);
+            let y = params.y.eval(audio_packet);
+            let radius = params.radius.eval(audio_packet);
+            let line_width = params.line_width.eval(audio_packet);
+            self.uniform_data.extend([
+                aspect_ratio,
+                x,
+                y,
+                radius,
+                line_width,
+                params.r.eval(audio_packet),
+                params.g.eval(audio_packet),
+                params.b.eval(audio_packet),
+                params.a.eval(audio_packet)
+            ]);
+            let outer = radius.abs() + line_width.abs();
+            bounds.push(Bounds {
+                origin: [x, y],
+                min: [x - outer, y - outer * aspect_ratio],
+                size: [outer * 2.0, outer * 2.0 * aspect_ratio]
+            });
+        }
+        renderer.queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&self.uniform_data));
     }
 
     fn draw(&self, render_pass: &mut RenderPass<'_>) {
@@ -177,6 +203,6 @@ impl ObjectRenderable for CircleRenderable {
         render_pass.set_bind_group(0, &self.bind_group, &[]);
         render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
         let max = VERTEX_COUNT as u32;
-        render_pass.draw(0..max, 0..1);
+        render_pass.draw(0..max, 0..(self.params.len() as u32));
     }
 }
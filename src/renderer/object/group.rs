@@ -0,0 +1,103 @@
+use crate::{
+    audio::AudioPacket, operation::Operation, renderer::{
+        Renderer, RenderPass, RenderParam, Mapping
+    }
+};
+use super::{ObjectRenderable, ObjectDesc, Bounds, transform::{Affine, Parent, TransformParameters}};
+
+/// A list of objects that move, rotate, scale and fade together.
+pub struct GroupRenderable {
+    params: GroupParameters,
+    children: Vec<Box<dyn ObjectRenderable>>,
+    child_bounds: Vec<Bounds>,
+    /// The group's own transform and opacity, from the last update.
+    own: Parent,
+    parent: Parent
+}
+
+/// The parameters of a group, taken from its mapping.
+pub struct GroupParameters {
+    x: Operation,
+    y: Operation,
+    a: Operation,
+    transform: TransformParameters,
+    children: Vec<ObjectDesc>
+}
+
+impl GroupParameters {
+    pub fn new(mapping: &mut Mapping, children: Vec<ObjectDesc>) -> Self {
+        Self {
+            x: mapping.get_or(RenderParam::X, 0.0),
+            y: mapping.get_or(RenderParam::Y, 0.0),
+            a: mapping.get_or(RenderParam::A, 1.0),
+            transform: TransformParameters::new(mapping),
+            children
+        }
+    }
+}
+
+impl GroupRenderable {
+    /// Create a new group, and all of the objects in it.
+    pub fn new(mut params: GroupParameters, renderer: &Renderer) -> Self {
+        let children = std::mem::take(&mut params.children).into_iter()
+            .map(|child| child.create(renderer))
+            .collect();
+        Self {
+            params,
+            children,
+            child_bounds: Vec::new(),
+            own: Parent::default(),
+            parent: Parent::default()
+        }
+    }
+
+    /// Pass the combined transform of this group and its parents on to the objects in the group.
+    fn update_children(&mut self, renderer: &Renderer, aspect_ratio: f32) {
+        let parent = Parent {
+            transform: self.own.transform.then(self.parent.transform),
+            opacity: self.own.opacity * self.parent.opacity
+        };
+        for child in &mut self.children {
+            child.set_parent(renderer, parent, aspect_ratio);
+        }
+    }
+}
+
+impl ObjectRenderable for GroupRenderable {
+    fn update(&mut self, renderer: &Renderer, audio_packet: &AudioPacket, aspect_ratio: f32, bounds: &mut Vec<Bounds>) {
+        let x = self.params.x.eval(audio_packet);
+        let y = self.params.y.eval(audio_packet);
+
+        // The objects are placed relative to the position of the group.
+        self.child_bounds.clear();
+        for child in &mut self.children {
+            child.update(renderer, audio_packet, aspect_ratio, &mut self.child_bounds);
+        }
+        let group_bounds = self.child_bounds.iter()
+            .copied()
+            .reduce(|a, b| a.union(&b))
+            .map_or(Bounds { origin: [x, y], min: [x, y], size: [0.0, 0.0] }, |bounds| Bounds {
+                origin: [x, y],
+                min: [bounds.min[0] + x, bounds.min[1] + y],
+                size: bounds.size
+            });
+        let transform = self.params.transform.eval(audio_packet, &group_bounds, aspect_ratio);
+        self.own = Parent {
+            transform: Affine::translate(x, y / aspect_ratio).then(transform),
+            opacity: self.params.a.eval(audio_packet)
+        };
+        self.update_children(renderer, aspect_ratio);
+        bounds.push(transform.apply_bounds(&group_bounds, aspect_ratio));
+    }
+
+    fn set_parent(&mut self, renderer: &Renderer, parent: Parent, aspect_ratio: f32) {
+        self.parent = parent;
+        self.update_children(renderer, aspect_ratio);
+    }
+
+    fn draw(&self, render_pass: &mut RenderPass<'_>) {
+        for child in &self.children {
+            child.draw(render_pass);
+        }
+    }
+}
@@ -0,0 +1,327 @@
+use std::{path::Path, rc::Rc};
+
+use bytemuck::{Zeroable, Pod};
+use wgpu::util::DeviceExt;
+
+use crate::{
+    audio::AudioPacket, operation::Operation, renderer::{
+        Renderer, RenderPass, RenderParam, Mapping, BlendMode
+    }
+};
+use super::{ObjectRenderable, ObjectType, Bounds, transform};
+
+const VERTEX_COUNT: usize = 4;
+/// The number of values in each copy's uniforms.
+const UNIFORM_COUNT: usize = 8;
+/// The largest texture that every GPU supports.
+const MAX_IMAGE_SIZE: u32 = 8192;
+
+#[derive(Zeroable, Pod, Clone, Copy)]
+#[repr(C)]
+struct Vertex {
+    pos: [f32; 2]
+}
+
+/// A decoded image file, as RGBA pixels.
+#[derive(Default)]
+pub struct ImageData {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>
+}
+
+impl ImageData {
+    /// Read and decode a PNG or JPEG file.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let image = image::open(path).map_err(|e| e.to_string())?.into_rgba8();
+        if image.width() > MAX_IMAGE_SIZE || image.height() > MAX_IMAGE_SIZE {
+            return Err(format!("image is {}x{}, but the largest size is {}x{}", image.width(), image.height(), MAX_IMAGE_SIZE, MAX_IMAGE_SIZE));
+        }
+        let (width, height) = image.dimensions();
+        let mut pixels = image.into_raw();
+        premultiply(&mut pixels);
+        Ok(Self {
+            width,
+            height,
+            pixels
+        })
+    }
+}
+
+/// Multiply the colour of each pixel by its alpha, so that filtering doesn't bring in the colour of transparent pixels.
+/// The pixels are sRGB, so this is done in linear light, which is how the texture is sampled.
+fn premultiply(pixels: &mut [u8]) {
+    let to_linear = |c: u8| {
+        let c = c as f32 / 255.0;
+        if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+    };
+    let to_srgb = |c: f32| {
+        let c = if c <= 0.0031308 { c * 12.92 } else { 1.055 * c.powf(1.0 / 2.4) - 0.055 };
+        (c * 255.0).round() as u8
+    };
+    let linear = (0..=255).map(to_linear).collect::<Vec<_>>();
+    for pixel in pixels.chunks_exact_mut(4) {
+        let alpha = pixel[3];
+        if alpha < 255 {
+            for c in &mut pixel[..3] {
+                *c = to_srgb(linear[*c as usize] * (alpha as f32 / 255.0));
+            }
+        }
+    }
+}
+
+/// An image drawn as a textured quad, which can have several copies.
+pub struct ImageRenderable {
+    /// One for each copy. Every copy shows the same image.
+    params: Vec<ImageParameters>,
+    pipeline: wgpu::RenderPipeline,
+    vertex_buffer: wgpu::Buffer,
+    uniform_data: Vec<f32>,
+    uniform_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup
+}
+
+/// The parameters of an image, taken from its mapping.
+pub struct ImageParameters {
+    image: Rc<ImageData>,
+    x: Operation,
+    y: Operation,
+    width: Operation,
+    /// If not specified, the height keeps the shape of the image.
+    height: Option<Operation>,
+    r: Operation,
+    g: Operation,
+    b: Operation,
+    a: Operation,
+}
+
+impl ImageParameters {
+    pub fn new(mapping: &mut Mapping) -> Self {
+        Self {
+            image: mapping.take_image(),
+            x: mapping.get(RenderParam::X),
+            y: mapping.get(RenderParam::Y),
+            width: mapping.get(RenderParam::Width),
+            height: mapping.get_optional(RenderParam::Height),
+            r: mapping.get_or(RenderParam::R, 1.0),
+            g: mapping.get_or(RenderParam::G, 1.0),
+            b: mapping.get_or(RenderParam::B, 1.0),
+            a: mapping.get_or(RenderParam::A, 1.0),
+        }
+    }
+}
+
+impl ImageRenderable {
+    pub fn create_pipeline(device: &wgpu::Device, transform_layout: &wgpu::BindGroupLayout, blend: BlendMode) -> wgpu::RenderPipeline {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                    ty: wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Storage { read_only: true }, has_dynamic_offset: false, min_binding_size: None },
+                    count: None
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false
+                    },
+                    count: None
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None
+                }
+            ]
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[&bind_group_layout, transform_layout],
+            push_constant_ranges: &[]
+        });
+        let shader_module = transform::create_shader_module(device, "image", include_str!("shaders/image.wgsl"));
+        let image_desc = wgpu::RenderPipelineDescriptor {
+            label: Some("image"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader_module,
+                entry_point: Some("vs_main"),
+                buffers: &[
+                    wgpu::VertexBufferLayout {
+                        array_stride: std::mem::size_of::<Vertex>() as u64,
+                        step_mode: wgpu::VertexStepMode::Vertex,
+                        attributes: &[
+                            wgpu::VertexAttribute {
+                                format: wgpu::VertexFormat::Float32x2,
+                                offset: 0,
+                                shader_location: 0
+                            }
+                        ]
+                    }
+                ],
+                compilation_options: Default::default()
+            },
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleStrip,
+                .. Default::default()
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader_module,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: wgpu::TextureFormat::Bgra8UnormSrgb,
+                    blend: Some(blend.blend_state()),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default()
+            }),
+            multiview: None,
+            cache: None
+        };
+        device.create_render_pipeline(&image_desc)
+    }
+
+    /// Create a new image to display on-screen, with a copy for each set of parameters.
+    pub fn new(params: Vec<ImageParameters>, blend: BlendMode, renderer: &Renderer) -> Self {
+        let image = params[0].image.clone();
+        let buf = [
+            Vertex{pos: [0.0, 0.0]},
+            Vertex{pos: [1.0, 0.0]},
+            Vertex{pos: [0.0, 1.0]},
+            Vertex{pos: [1.0, 1.0]}
+        ];
+        let vertex_buffer = renderer.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: None,
+            usage: wgpu::BufferUsages::VERTEX,
+            contents: bytemuck::cast_slice(&buf)
+        });
+        let uniform_buffer = renderer.device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: (std::mem::size_of::<f32>() * UNIFORM_COUNT * params.len()) as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false
+        });
+        let texture = renderer.device.create_texture_with_data(&renderer.queue, &wgpu::TextureDescriptor {
+            label: None,
+            size: wgpu::Extent3d {
+                width: image.width, height: image.height, depth_or_array_layers: 1
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[]
+        }, wgpu::util::TextureDataOrder::LayerMajor, &image.pixels);
+        let view = texture.create_view(&Default::default());
+        let sampler = renderer.device.create_sampler(&wgpu::SamplerDescriptor {
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let pipeline = renderer.get_render_pipeline(ObjectType::Image, blend);
+        let bind_group_layout = pipeline.get_bind_group_layout(0);
+        let bind_group = renderer.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding()
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&view)
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&sampler)
+                }
+            ]
+        });
+        Self {
+            params,
+            pipeline,
+            vertex_buffer,
+            uniform_data: Vec::new(),
+            uniform_buffer,
+            bind_group
+        }
+    }
+}
+
+impl ObjectRenderable for ImageRenderable {
+    fn update(&mut self, renderer: &Renderer, audio_packet: &AudioPacket, aspect_ratio: f32, bounds: &mut Vec<Bounds>) {
+        self.uniform_data.clear();
+        for params in &mut self.params {
+            let x = params.x.eval(audio_packet);
+            let y = params.y.eval(audio_packet);
+            let width = params.width.eval(audio_packet);
+            let height = match &mut params.height {
+                Some(height) => height.eval(audio_packet),
+                // Keep the shape of the image on screen.
+                None => width * (params.image.height as f32) / (params.image.width as f32) * aspect_ratio
+            };
+            self.uniform_data.extend([
+                x,
+                y,
+                width,
+                height,
+                params.r.eval(audio_packet),
+                params.g.eval(audio_packet),
+                params.b.eval(audio_packet),
+                params.a.eval(audio_packet)
+            ]);
+            bounds.push(Bounds {
+                origin: [x, y],
+                min: [x, y],
+                size: [width, height]
+            });
+        }
+        renderer.queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&self.uniform_data));
+    }
+
+    fn draw(&self, render_pass: &mut RenderPass<'_>) {
+        let render_pass = render_pass.render_pass.as_mut().unwrap();
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        let max = VERTEX_COUNT as u32;
+        render_pass.draw(0..max, 0..(self.params.len() as u32));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn premultiply_in_linear_light() {
+        let mut pixels = vec![
+            200, 100, 50, 255,
+            255, 255, 255, 0,
+            255, 255, 255, 128,
+            0, 0, 0, 128,
+        ];
+        premultiply(&mut pixels);
+        assert_eq!(pixels, [
+            200, 100, 50, 255,
+            0, 0, 0, 0,
+            // Half of white is 0.5 in linear light, which is 188 in sRGB.
+            188, 188, 188, 128,
+            0, 0, 0, 128,
+        ]);
+    }
+}
@@ -0,0 +1,81 @@
+use crate::{
+    audio::AudioPacket, operation::Operation, renderer::{
+        Renderer, RenderPass, RenderParam, Mapping, BlendMode
+    }
+};
+use super::{ObjectRenderable, ObjectType, Bounds, shape::{Shape, Stroke, Join, Cap}};
+
+/// A straight line between two points.
+pub struct LineRenderable {
+    params: LineParameters,
+    shape: Shape
+}
+
+/// The parameters of a line, taken from its mapping.
+pub struct LineParameters {
+    x1: Operation,
+    y1: Operation,
+    x2: Operation,
+    y2: Operation,
+    line_width: Operation,
+    r: Operation,
+    g: Operation,
+    b: Operation,
+    a: Operation,
+    cap: Cap
+}
+
+impl LineParameters {
+    pub fn new(mapping: &mut Mapping) -> Self {
+        Self {
+            x1: mapping.get(RenderParam::X1),
+            y1: mapping.get(RenderParam::Y1),
+            x2: mapping.get(RenderParam::X2),
+            y2: mapping.get(RenderParam::Y2),
+            line_width: mapping.get(RenderParam::LineWidth),
+            r: mapping.get(RenderParam::R),
+            g: mapping.get(RenderParam::G),
+            b: mapping.get(RenderParam::B),
+            a: mapping.get_or(RenderParam::A, 1.0),
+            cap: mapping.take_cap()
+        }
+    }
+}
+
+impl LineRenderable {
+    /// Create a new line to display on-screen.
+    pub fn new(params: LineParameters, blend: BlendMode, renderer: &Renderer) -> Self {
+        Self {
+            params,
+            shape: Shape::new(ObjectType::Line, blend, renderer)
+        }
+    }
+}
+
+impl ObjectRenderable for LineRenderable {
+    fn update(&mut self, renderer: &Renderer, audio_packet: &AudioPacket, aspect_ratio: f32, bounds: &mut Vec<Bounds>) {
+        let x1 = self.params.x1.eval(audio_packet);
+        let y1 = self.params.y1.eval(audio_packet);
+        let x2 = self.params.x2.eval(audio_packet);
+        let y2 = self.params.y2.eval(audio_packet);
+        let stroke = Stroke {
+            width: self.params.line_width.eval(audio_packet),
+            join: Join::default(),
+            cap: self.params.cap
+        };
+
+        self.shape.clear();
+        self.shape.stroke(&[[x1, y1 / aspect_ratio], [x2, y2 / aspect_ratio]], false, &stroke);
+        let colour = [
+            self.params.r.eval(audio_packet),
+            self.params.g.eval(audio_packet),
+            self.params.b.eval(audio_packet),
+            self.params.a.eval(audio_packet)
+        ];
+        bounds.push(self.shape.write(renderer, aspect_ratio, colour, [x1, y1]));
+    }
+
+    fn draw(&self, render_pass: &mut RenderPass<'_>) {
+        self.shape.draw(render_pass);
+    }
+}
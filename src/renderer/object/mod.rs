@@ -1,22 +1,151 @@
 pub mod circle;
 pub mod quad;
+pub mod waveform;
+pub mod spectrum;
+pub mod text;
+pub mod image;
+pub mod group;
+pub mod line;
+pub mod polygon;
+pub mod polyline;
+pub mod shape;
+pub mod transform;
 
 use crate::audio::AudioPacket;
-use crate::renderer::{Renderer, RenderPass};
+use crate::renderer::{Renderer, RenderPass, Mapping, CreationError, BlendMode};
+pub use transform::{Bounds, Parent};
+use transform::{TransformParameters, TransformedObject};
 
 pub trait ObjectRenderable {
     // TODO: store graphics params somewhere?
     /// Update the renderable with new parameters.
-    fn update(&mut self, renderer: &Renderer, audio_packet: &AudioPacket, aspect_ratio: f32);
+    /// Adds the area that each copy of the object covers to `bounds`, before it is transformed by any groups that it is in.
+    fn update(&mut self, renderer: &Renderer, audio_packet: &AudioPacket, aspect_ratio: f32, bounds: &mut Vec<Bounds>);
+
+    /// Set the transform of the groups that the renderable is in.
+    /// This is called by the group after each update, because the group's transform depends on the area that its objects cover.
+    fn set_parent(&mut self, _renderer: &Renderer, _parent: Parent, _aspect_ratio: f32) {}
 
     /// Draw the renderable using the provided render pass.
     fn draw(&self, render_pass: &mut RenderPass<'_>);
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, Hash, strum::EnumString, strum::Display)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, strum::EnumString, strum::Display)]
+#[strum(serialize_all = "lowercase")]
 pub enum ObjectType {
     #[strum(ascii_case_insensitive)]
     Circle,
     #[strum(ascii_case_insensitive)]
-    Quad
-}
\ No newline at end of file
+    Quad,
+    #[strum(ascii_case_insensitive)]
+    Waveform,
+    #[strum(ascii_case_insensitive)]
+    Spectrum,
+    #[strum(ascii_case_insensitive)]
+    Text,
+    #[strum(ascii_case_insensitive)]
+    Image,
+    #[strum(ascii_case_insensitive)]
+    Line,
+    #[strum(ascii_case_insensitive)]
+    Polygon,
+    #[strum(ascii_case_insensitive)]
+    Polyline
+}
+
+impl ObjectType {
+    /// Whether the object can be repeated. Repeated objects draw all of their copies at once,
+    /// so this is only possible for objects that don't already draw several instances.
+    pub fn can_repeat(self) -> bool {
+        matches!(self, ObjectType::Circle | ObjectType::Quad | ObjectType::Image)
+    }
+}
+
+/// The largest number of copies of a repeated object.
+pub const MAX_COPIES: usize = 10000;
+
+/// An object whose parameters have been taken from its mapping.
+/// Checking the parameters doesn't need a GPU, so scripts can be checked without one.
+pub struct ObjectDesc {
+    params: ObjectParameters,
+    /// One for each copy. Groups keep their transform in their own parameters.
+    transform: Vec<TransformParameters>,
+    blend: BlendMode
+}
+
+/// The parameters of objects that can be repeated have one set for each copy.
+enum ObjectParameters {
+    Circle(Vec<circle::CircleParameters>),
+    Quad(Vec<quad::QuadParameters>),
+    Waveform(Box<waveform::WaveformParameters>),
+    Spectrum(Box<spectrum::SpectrumParameters>),
+    Text(Box<text::TextParameters>),
+    Image(Vec<image::ImageParameters>),
+    Line(Box<line::LineParameters>),
+    Polygon(Box<polygon::PolygonParameters>),
+    Polyline(Box<polyline::PolylineParameters>),
+    Group(Box<group::GroupParameters>)
+}
+
+impl ObjectDesc {
+    /// Take the parameters for an object from a mapping for each copy. Objects that aren't repeated have one mapping.
+    /// Fails with every missing parameter, and every parameter that the object doesn't use.
+    /// Only the errors in the first copy that has any are returned, since every copy has the same parameters.
+    pub fn new(object_type: ObjectType, mut mappings: Vec<Mapping>) -> Result<Self, Vec<CreationError>> {
+        assert!(mappings.len() == 1 || object_type.can_repeat(), "{} can't be repeated", object_type);
+        let params = match object_type {
+            ObjectType::Circle => ObjectParameters::Circle(mappings.iter_mut().map(circle::CircleParameters::new).collect()),
+            ObjectType::Quad => ObjectParameters::Quad(mappings.iter_mut().map(quad::QuadParameters::new).collect()),
+            ObjectType::Waveform => ObjectParameters::Waveform(Box::new(waveform::WaveformParameters::new(&mut mappings[0]))),
+            ObjectType::Spectrum => ObjectParameters::Spectrum(Box::new(spectrum::SpectrumParameters::new(&mut mappings[0]))),
+            ObjectType::Text => ObjectParameters::Text(Box::new(text::TextParameters::new(&mut mappings[0]))),
+            ObjectType::Image => ObjectParameters::Image(mappings.iter_mut().map(image::ImageParameters::new).collect()),
+            ObjectType::Line => ObjectParameters::Line(Box::new(line::LineParameters::new(&mut mappings[0]))),
+            ObjectType::Polygon => ObjectParameters::Polygon(Box::new(polygon::PolygonParameters::new(&mut mappings[0]))),
+            ObjectType::Polyline => ObjectParameters::Polyline(Box::new(polyline::PolylineParameters::new(&mut mappings[0]))),
+        };
+        // Every copy has the same blend mode, because they are all drawn at once.
+        let blend = mappings.iter_mut().fold(BlendMode::default(), |_, mapping| mapping.take_blend());
+        let transform = mappings.iter_mut()
+            .map(TransformParameters::new)
+            .collect();
+        for mapping in &mappings {
+            mapping.check_parameters()?;
+        }
+        Ok(Self {
+            params,
+            transform,
+            blend
+        })
+    }
+
+    /// Take the parameters for a group from a mapping.
+    /// Groups can't have a blend mode, because each of their objects is drawn with its own.
+    pub fn group(mut mapping: Mapping, children: Vec<ObjectDesc>) -> Result<Self, Vec<CreationError>> {
+        let params = group::GroupParameters::new(&mut mapping, children);
+        mapping.check_parameters()?;
+        Ok(Self {
+            params: ObjectParameters::Group(Box::new(params)),
+            transform: Vec::new(),
+            blend: BlendMode::default()
+        })
+    }
+
+    /// Create the GPU resources for the object.
+    pub fn create(self, renderer: &Renderer) -> Box<dyn ObjectRenderable> {
+        let object: Box<dyn ObjectRenderable> = match self.params {
+            ObjectParameters::Circle(params) => Box::new(circle::CircleRenderable::new(params, self.blend, renderer)),
+            ObjectParameters::Quad(params) =>   Box::new(quad::QuadRenderable::new(params, self.blend, renderer)),
+            ObjectParameters::Waveform(params) => Box::new(waveform::WaveformRenderable::new(*params, self.blend, renderer)),
+            ObjectParameters::Spectrum(params) => Box::new(spectrum::SpectrumRenderable::new(*params, self.blend, renderer)),
+            ObjectParameters::Text(params) =>   Box::new(text::TextRenderable::new(*params, self.blend, renderer)),
+            ObjectParameters::Image(params) =>  Box::new(image::ImageRenderable::new(params, self.blend, renderer)),
+            ObjectParameters::Line(params) =>   Box::new(line::LineRenderable::new(*params, self.blend, renderer)),
+            ObjectParameters::Polygon(params) => Box::new(polygon::PolygonRenderable::new(*params, self.blend, renderer)),
+            ObjectParameters::Polyline(params) => Box::new(polyline::PolylineRenderable::new(*params, self.blend, renderer)),
+            // Groups are transformed by passing their transform on to their objects.
+            ObjectParameters::Group(params) => return Box::new(group::GroupRenderable::new(*params, renderer)),
+        };
+        Box::new(TransformedObject::new(object, self.transform, renderer))
+    }
+}
@@ -0,0 +1,184 @@
+use std::f32::consts::{FRAC_PI_2, TAU};
+
+use crate::{
+    audio::AudioPacket, operation::Operation, renderer::{
+        Renderer, RenderPass, RenderParam, Mapping, BlendMode
+    }
+};
+use super::{ObjectRenderable, ObjectType, Bounds, shape::{Shape, Mesh, Stroke, Join, Cap}};
+
+/// The largest number of sides that a polygon can have.
+const MAX_SIDES: f32 = 1000.0;
+
+/// A regular polygon, which is either filled or outlined.
+pub struct PolygonRenderable {
+    params: PolygonParameters,
+    /// The corners from the last update, with the y axis scaled so that both axes have the same units.
+    points: Vec<[f32; 2]>,
+    shape: Shape
+}
+
+/// The parameters of a polygon, taken from its mapping.
+pub struct PolygonParameters {
+    x: Operation,
+    y: Operation,
+    radius: Operation,
+    sides: Operation,
+    /// If not specified, the polygon is filled.
+    line_width: Option<Operation>,
+    r: Operation,
+    g: Operation,
+    b: Operation,
+    a: Operation,
+    join: Join
+}
+
+impl PolygonParameters {
+    pub fn new(mapping: &mut Mapping) -> Self {
+        Self {
+            x: mapping.get(RenderParam::X),
+            y: mapping.get(RenderParam::Y),
+            radius: mapping.get(RenderParam::Radius),
+            sides: mapping.get(RenderParam::Sides),
+            line_width: mapping.get_optional(RenderParam::LineWidth),
+            r: mapping.get(RenderParam::R),
+            g: mapping.get(RenderParam::G),
+            b: mapping.get(RenderParam::B),
+            a: mapping.get_or(RenderParam::A, 1.0),
+            join: mapping.take_join()
+        }
+    }
+}
+
+impl PolygonRenderable {
+    /// Create a new polygon to display on-screen.
+    pub fn new(params: PolygonParameters, blend: BlendMode, renderer: &Renderer) -> Self {
+        Self {
+            params,
+            points: Vec::new(),
+            shape: Shape::new(ObjectType::Polygon, blend, renderer)
+        }
+    }
+}
+
+/// The number of sides to draw, rounded to a whole number from 3 to `MAX_SIDES`. NaN gives a triangle.
+fn side_count(sides: f32) -> usize {
+    if sides.is_nan() {
+        return 3;
+    }
+    sides.round().clamp(3.0, MAX_SIDES) as usize
+}
+
+/// Add a polygon to the mesh, which is outlined if it has a stroke and filled otherwise.
+/// The corners are left in `points`, starting with the one at the top.
+fn build(mesh: &mut Mesh, points: &mut Vec<[f32; 2]>, centre: [f32; 2], radius: f32, sides: f32, stroke: Option<&Stroke>) {
+    let sides = side_count(sides);
+    points.clear();
+    points.extend((0..sides).map(|i| {
+        let angle = FRAC_PI_2 + TAU * (i as f32) / (sides as f32);
+        [centre[0] + radius * angle.cos(), centre[1] + radius * angle.sin()]
+    }));
+
+    match stroke {
+        Some(stroke) => mesh.stroke(points, true, stroke),
+        None => mesh.fill(centre, points)
+    }
+}
+
+impl ObjectRenderable for PolygonRenderable {
+    fn update(&mut self, renderer: &Renderer, audio_packet: &AudioPacket, aspect_ratio: f32, bounds: &mut Vec<Bounds>) {
+        let x = self.params.x.eval(audio_packet);
+        let y = self.params.y.eval(audio_packet);
+        let radius = self.params.radius.eval(audio_packet);
+        let sides = self.params.sides.eval(audio_packet);
+        let stroke = self.params.line_width.as_mut().map(|line_width| Stroke {
+            width: line_width.eval(audio_packet),
+            join: self.params.join,
+            cap: Cap::default()
+        });
+
+        self.shape.clear();
+        build(self.shape.mesh(), &mut self.points, [x, y / aspect_ratio], radius, sides, stroke.as_ref());
+        let colour = [
+            self.params.r.eval(audio_packet),
+            self.params.g.eval(audio_packet),
+            self.params.b.eval(audio_packet),
+            self.params.a.eval(audio_packet)
+        ];
+        bounds.push(self.shape.write(renderer, aspect_ratio, colour, [x, y]));
+    }
+
+    fn draw(&self, render_pass: &mut RenderPass<'_>) {
+        self.shape.draw(render_pass);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::SQRT_2;
+
+    use super::*;
+    use super::super::shape::tests::{vertices, area, assert_close};
+
+    fn polygon(sides: f32, stroke: Option<&Stroke>) -> (Vec<[f32; 2]>, Vec<[f32; 2]>) {
+        let mut mesh = Mesh::default();
+        let mut points = Vec::new();
+        build(&mut mesh, &mut points, [0.5, 0.25], 1.0, sides, stroke);
+        (points, vertices(&mesh))
+    }
+
+    #[test]
+    fn sides_are_clamped() {
+        let cases = [
+            (3.0, 3), (2.0, 3), (-4.0, 3), (f32::NEG_INFINITY, 3), (f32::NAN, 3),
+            (4.4, 4), (5.6, 6), (MAX_SIDES + 1.0, 1000), (f32::INFINITY, 1000)
+        ];
+        for (sides, expected) in cases {
+            let (points, vertices) = polygon(sides, None);
+            assert_eq!(points.len(), expected, "{sides} sides");
+            assert_eq!(vertices.len(), expected * 3, "{sides} sides");
+        }
+    }
+
+    #[test]
+    fn corners_start_at_the_top() {
+        for sides in [3.0, 4.0, 7.0, f32::NAN] {
+            let (points, _) = polygon(sides, None);
+            assert_close(points[0][0], 0.5);
+            assert_close(points[0][1], 1.25);
+            for point in points {
+                assert_close((point[0] - 0.5).hypot(point[1] - 0.25), 1.0);
+            }
+        }
+    }
+
+    #[test]
+    fn filled_polygons_cover_their_area() {
+        for sides in [3, 4, 6, 100] {
+            let (_, vertices) = polygon(sides as f32, None);
+            let expected = 0.5 * (sides as f32) * (TAU / sides as f32).sin();
+            assert_close(area(&vertices), expected);
+            assert!(vertices.contains(&[0.5, 0.25]));
+        }
+    }
+
+    #[test]
+    fn outlined_polygons_leave_the_middle_empty() {
+        // The square stands on a corner. Its mitred outline is the difference between two squares, with sides of √2 ± the width.
+        let stroke = Stroke { width: 0.1, join: Join::Miter, cap: Cap::default() };
+        let (_, vertices) = polygon(4.0, Some(&stroke));
+        assert_close(area(&vertices), 4.0 * SQRT_2 * 0.1);
+        assert!(!vertices.contains(&[0.5, 0.25]));
+        for vertex in vertices {
+            let distance = (vertex[0] - 0.5).abs() + (vertex[1] - 0.25).abs();
+            assert!((distance - 1.0).abs() < 0.05 * SQRT_2 + 1e-3, "{vertex:?}");
+        }
+
+        let (points, vertices) = polygon(f32::NAN, Some(&stroke));
+        assert_eq!(points.len(), 3);
+        assert!(!vertices.is_empty());
+
+        let stroke = Stroke { width: 0.0, ..stroke };
+        assert!(polygon(4.0, Some(&stroke)).1.is_empty());
+    }
+}
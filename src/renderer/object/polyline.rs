@@ -0,0 +1,82 @@
+use crate::{
+    audio::AudioPacket, operation::Operation, renderer::{
+        Renderer, RenderPass, RenderParam, Mapping, BlendMode
+    }
+};
+use super::{ObjectRenderable, ObjectType, Bounds, shape::{Shape, Stroke, Join, Cap}};
+
+/// A line through a list of points.
+pub struct PolylineRenderable {
+    params: PolylineParameters,
+    /// The points from the last update, with the y axis scaled so that both axes have the same units.
+    points: Vec<[f32; 2]>,
+    shape: Shape
+}
+
+/// The parameters of a polyline, taken from its mapping.
+pub struct PolylineParameters {
+    points: Vec<[Operation; 2]>,
+    line_width: Operation,
+    r: Operation,
+    g: Operation,
+    b: Operation,
+    a: Operation,
+    join: Join,
+    cap: Cap
+}
+
+impl PolylineParameters {
+    pub fn new(mapping: &mut Mapping) -> Self {
+        Self {
+            points: mapping.take_points(),
+            line_width: mapping.get(RenderParam::LineWidth),
+            r: mapping.get(RenderParam::R),
+            g: mapping.get(RenderParam::G),
+            b: mapping.get(RenderParam::B),
+            a: mapping.get_or(RenderParam::A, 1.0),
+            join: mapping.take_join(),
+            cap: mapping.take_cap()
+        }
+    }
+}
+
+impl PolylineRenderable {
+    /// Create a new polyline to display on-screen.
+    pub fn new(params: PolylineParameters, blend: BlendMode, renderer: &Renderer) -> Self {
+        Self {
+            points: Vec::with_capacity(params.points.len()),
+            params,
+            shape: Shape::new(ObjectType::Polyline, blend, renderer)
+        }
+    }
+}
+
+impl ObjectRenderable for PolylineRenderable {
+    fn update(&mut self, renderer: &Renderer, audio_packet: &AudioPacket, aspect_ratio: f32, bounds: &mut Vec<Bounds>) {
+        self.points.clear();
+        for [x, y] in &mut self.params.points {
+            self.points.push([x.eval(audio_packet), y.eval(audio_packet) / aspect_ratio]);
+        }
+        let stroke = Stroke {
+            width: self.params.line_width.eval(audio_packet),
+            join: self.params.join,
+            cap: self.params.cap
+        };
+
+        self.shape.clear();
+        self.shape.stroke(&self.points, false, &stroke);
+        let colour = [
+            self.params.r.eval(audio_packet),
+            self.params.g.eval(audio_packet),
+            self.params.b.eval(audio_packet),
+            self.params.a.eval(audio_packet)
+        ];
+        // The line is rotated and scaled around its first point.
+        let [x, y] = self.points[0];
+        bounds.push(self.shape.write(renderer, aspect_ratio, colour, [x, y * aspect_ratio]));
+    }
+
+    fn draw(&self, render_pass: &mut RenderPass<'_>) {
+        self.shape.draw(render_pass);
+    }
+}
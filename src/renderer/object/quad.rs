@@ -3,12 +3,14 @@ use wgpu::util::DeviceExt;
 
 use crate::{
     audio::AudioPacket, operation::Operation, renderer::{
-        Renderer, RenderPass, RenderParam, Mapping, CreationError
+        Renderer, RenderPass, RenderParam, Mapping, BlendMode
     }
 };
-use super::{ObjectRenderable, ObjectType};
+use super::{ObjectRenderable, ObjectType, Bounds, transform};
 
 const VERTEX_COUNT: usize = 4;
+/// The number of values in each copy's uniforms.
+const UNIFORM_COUNT: usize = 8;
 
 #[derive(Zeroable, Pod, Clone, Copy)]
 #[repr(C)]
@@ -16,16 +18,19 @@ struct Vertex {
     pos: [f32; 2]
 }
 
-/// An instance of a quad.
+/// An instance of a quad, which can have several copies.
 pub struct QuadRenderable {
-    params: QuadParameters,
+    /// One for each copy.
+    params: Vec<QuadParameters>,
     pipeline: wgpu::RenderPipeline,
     vertex_buffer: wgpu::Buffer,
+    uniform_data: Vec<f32>,
     uniform_buffer: wgpu::Buffer,
     bind_group: wgpu::BindGroup
 }
 
-struct QuadParameters {
+/// The parameters of a quad, taken from its mapping.
+pub struct QuadParameters {
     x: Operation,
     y: Operation,
     width: Operation,
@@ -33,27 +38,43 @@ struct QuadParameters {
     r: Operation,
     g: Operation,
     b: Operation,
+    a: Operation,
+}
+
+impl QuadParameters {
+    pub fn new(mapping: &mut Mapping) -> Self {
+        Self {
+            x: mapping.get(RenderParam::X),
+            y: mapping.get(RenderParam::Y),
+            width: mapping.get(RenderParam::Width),
+            height: mapping.get(RenderParam::Height),
+            r: mapping.get(RenderParam::R),
+            g: mapping.get(RenderParam::G),
+            b: mapping.get(RenderParam::B),
+            a: mapping.get_or(RenderParam::A, 1.0),
+        }
+    }
 }
 
 impl QuadRenderable {
-    pub fn create_pipeline(device: &wgpu::Device) -> wgpu::RenderPipeline {
+    pub fn create_pipeline(device: &wgpu::Device, transform_layout: &wgpu::BindGroupLayout, blend: BlendMode) -> wgpu::RenderPipeline {
         let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             label: None,
             entries: &[
                 wgpu::BindGroupLayoutEntry {
                     binding: 0,
                     visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
-                    ty: wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Uniform, has_dynamic_offset: false, min_binding_size: None },
+                    ty: wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Storage { read_only: true }, has_dynamic_offset: false, min_binding_size: None },
                     count: None
                 }
             ]
         });
         let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: None,
-            bind_group_layouts: &[&bind_group_layout],
+            bind_group_layouts: &[&bind_group_layout, transform_layout],
             push_constant_ranges: &[]
         });
-        let shader_module = device.create_shader_module(wgpu::include_wgsl!("shaders/quad.wgsl"));
+        let shader_module = transform::create_shader_module(device, "quad", include_str!("shaders/quad.wgsl"));
         let circle_desc = wgpu::RenderPipelineDescriptor {
             label: Some("quad"),
             layout: Some(&pipeline_layout),
@@ -90,7 +111,7 @@ impl QuadRenderable {
                 entry_point: Some("fs_main"),
                 targets: &[Some(wgpu::ColorTargetState {
                     format: wgpu::TextureFormat::Bgra8UnormSrgb,
-                    blend: None,
+                    blend: Some(blend.blend_state()),
                     write_mask: wgpu::ColorWrites::ALL,
                 })],
                 compilation_options: Default::default()
@@ -101,8 +122,8 @@ impl QuadRenderable {
         device.create_render_pipeline(&circle_desc)
     }
 
-    /// Create a new quad to display on-screen.
-    pub fn new(mut mapping: Mapping, renderer: &Renderer) -> Result<Self, CreationError> {
+    /// Create a new quad to display on-screen, with a copy for each set of parameters.
+    pub fn new(params: Vec<QuadParameters>, blend: BlendMode, renderer: &Renderer) -> Self {
         // TODO: share vertex buffer?
         let buf = [
             Vertex{pos: [0.0, 0.0]},
@@ -113,15 +134,15 @@ impl QuadRenderable {
         let vertex_buffer = renderer.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: None,
             usage: wgpu::BufferUsages::VERTEX,
-            contents: &bytemuck::cast_slice(&buf)
+            contents: bytemuck::cast_slice(&buf)
         });
         let uniform_buffer = renderer.device.create_buffer(&wgpu::BufferDescriptor {
             label: None,
-            size: (std::mem::size_of::<f32>() as u64) * 8,
-            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            size: (std::mem::size_of::<f32>() * UNIFORM_COUNT * params.len()) as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
             mapped_at_creation: false
         });
-        let pipeline = renderer.get_render_pipeline(ObjectType::Quad);
+        let pipeline = renderer.get_render_pipeline(ObjectType::Quad, blend);
         let bind_group_layout = pipeline.get_bind_group_layout(0);
         let bind_group = renderer.device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: None,
@@ -133,38 +154,42 @@ impl QuadRenderable {
                 }
             ]
         });
-        let params = QuadParameters {
-            x: mapping.get(RenderParam::X)?,
-            y: mapping.get(RenderParam::Y)?,
-            width: mapping.get(RenderParam::Width)?,
-            height: mapping.get(RenderParam::Height)?,
-            r: mapping.get(RenderParam::R)?,
-            g: mapping.get(RenderParam::G)?,
-            b: mapping.get(RenderParam::B)?,
-        };
-        mapping.check_extra_parameters()?;
-        Ok(Self {
+        Self {
             params,
             pipeline,
             vertex_buffer,
+            uniform_data: Vec::new(),
             uniform_buffer,
             bind_group
-        })
+        }
     }
 }
 
 impl ObjectRenderable for QuadRenderable {
-    fn update(&mut self, renderer: &Renderer, audio_packet: &AudioPacket, _aspect_ratio: f32) {
-        let uniform_data = [
-            self.params.x.eval(audio_packet),
-            self.params.y.eval(audio_packet),
-            self.params.width.eval(audio_packet),
-            self.params.height.eval(audio_packet),
-            self.params.r.eval(audio_packet),
-            self.params.g.eval(audio_packet),
-            self.params.b.eval(audio_packet)
-        ];
-        renderer.queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&uniform_data));
+    fn update(&mut self, renderer: &Renderer, audio_packet: &AudioPacket, _aspect_ratio: f32, bounds: &mut Vec<Bounds>) {
+        self.uniform_data.clear();
+        for params in &mut self.params {
+            let x = params.x.eval(audio_packet);
+            let y = params.y.eval(audio_packet);
+            let width = params.width.eval(audio_packet);
+            let height = params.height.eval(audio_packet);
+            self.uniform_data.extend([
+                x,
+                y,
+                width,
+                height,
+                params.r.eval(audio_packet),
+                params.g.eval(audio_packet),
+                params.b.eval(audio_packet),
+                params.a.eval(audio_packet)
+            ]);
+            bounds.push(Bounds {
+                origin: [x, y],
+                min: [x, y],
+                size: [width, height]
+            });
+        }
+        renderer.queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&self.uniform_data));
     }
 
     fn draw(&self, render_pass: &mut RenderPass<'_>) {
@@ -173,6 +198,6 @@ impl ObjectRenderable for QuadRenderable {
         render_pass.set_bind_group(0, &self.bind_group, &[]);
         render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
         let max = VERTEX_COUNT as u32;
-        render_pass.draw(0..max, 0..1);
+        render_pass.draw(0..max, 0..(self.params.len() as u32));
     }
 }
@@ -1,10 +1,12 @@
 struct VertexInput {
     @location(0) pos: vec2<f32>,
-    @builtin(vertex_index) index: u32
+    @builtin(vertex_index) index: u32,
+    @builtin(instance_index) copy: u32
 }
 
 struct VertexOutput {
-    @builtin(position) pos: vec4<f32>
+    @builtin(position) pos: vec4<f32>,
+    @location(0) @interpolate(flat) copy: u32
 }
 
 struct Uniforms {
@@ -16,24 +18,30 @@ struct Uniforms {
     line_width: f32,
     r: f32,
     g: f32,
-    b: f32
+    b: f32,
+    a: f32
 }
 
-@group(0) @binding(0) var<uniform> params: Uniforms;
+// One for each copy of the circle.
+@group(0) @binding(0) var<storage, read> params: array<Uniforms>;
 
 @vertex fn vs_main(
     vertex: VertexInput
 ) -> VertexOutput {
+    let p = params[vertex.copy];
     let outer_circle = (vertex.index & 1u) == 1u;
-    let radius = params.radius + select(params.line_width, -params.line_width, outer_circle);
+    let radius = p.radius + select(p.line_width, -p.line_width, outer_circle);
     var pos = vertex.pos * radius;
-    pos.y *= params.aspect_ratio;
-    pos += vec2<f32>(params.x, params.y);
+    pos.y *= p.aspect_ratio;
+    pos += vec2<f32>(p.x, p.y);
     var out: VertexOutput;
-    out.pos = vec4<f32>(pos, 0.0, 1.0);
+    out.pos = vec4<f32>(apply_transform(pos, vertex.copy), 0.0, 1.0);
+    out.copy = vertex.copy;
     return out;
 }
 
 @fragment fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    return vec4<f32>(params.r, params.g, params.b, 1.0);
-}
\ No newline at end of file
+    let p = params[in.copy];
+    // Output premultiplied alpha.
+    return apply_opacity(vec4<f32>(vec3<f32>(p.r, p.g, p.b) * p.a, p.a), in.copy);
+}
@@ -0,0 +1,46 @@
+struct VertexInput {
+    @location(0) pos: vec2<f32>,
+    @builtin(instance_index) copy: u32
+}
+
+struct VertexOutput {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) @interpolate(flat) copy: u32
+}
+
+struct Uniforms {
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+    r: f32,
+    g: f32,
+    b: f32,
+    a: f32
+}
+
+// One for each copy of the image.
+@group(0) @binding(0) var<storage, read> params: array<Uniforms>;
+@group(0) @binding(1) var image: texture_2d<f32>;
+@group(0) @binding(2) var image_sampler: sampler;
+
+@vertex fn vs_main(
+    vertex: VertexInput
+) -> VertexOutput {
+    let p = params[vertex.copy];
+    let pos = vec2<f32>(p.x, p.y) + vertex.pos * vec2<f32>(p.width, p.height);
+    var out: VertexOutput;
+    out.pos = vec4<f32>(apply_transform(pos, vertex.copy), 0.0, 1.0);
+    // Images are stored top row first.
+    out.uv = vec2<f32>(vertex.pos.x, 1.0 - vertex.pos.y);
+    out.copy = vertex.copy;
+    return out;
+}
+
+@fragment fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let p = params[in.copy];
+    let colour = textureSample(image, image_sampler, in.uv);
+    // The image is already premultiplied, so its colour only needs the opacity of the copy.
+    return apply_opacity(vec4<f32>(colour.rgb * vec3<f32>(p.r, p.g, p.b) * p.a, colour.a * p.a), in.copy);
+}
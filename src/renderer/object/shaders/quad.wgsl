@@ -1,9 +1,11 @@
 struct VertexInput {
-    @location(0) pos: vec2<f32>
+    @location(0) pos: vec2<f32>,
+    @builtin(instance_index) copy: u32
 }
 
 struct VertexOutput {
-    @builtin(position) pos: vec4<f32>
+    @builtin(position) pos: vec4<f32>,
+    @location(0) @interpolate(flat) copy: u32
 }
 
 struct Uniforms {
@@ -13,21 +15,27 @@ struct Uniforms {
     height: f32,
     r: f32,
     g: f32,
-    b: f32
+    b: f32,
+    a: f32
 }
 
-@group(0) @binding(0) var<uniform> params: Uniforms;
+// One for each copy of the quad.
+@group(0) @binding(0) var<storage, read> params: array<Uniforms>;
 
 @vertex fn vs_main(
     vertex: VertexInput
 ) -> VertexOutput {
-    let x = params.x + vertex.pos.x * params.width;
-    let y = params.y + vertex.pos.y * params.height;
+    let p = params[vertex.copy];
+    let x = p.x + vertex.pos.x * p.width;
+    let y = p.y + vertex.pos.y * p.height;
     var out: VertexOutput;
-    out.pos = vec4<f32>(x, y, 0.0, 1.0);
+    out.pos = vec4<f32>(apply_transform(vec2<f32>(x, y), vertex.copy), 0.0, 1.0);
+    out.copy = vertex.copy;
     return out;
 }
 
 @fragment fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    return vec4<f32>(params.r, params.g, params.b, 1.0);
-}
\ No newline at end of file
+    let p = params[in.copy];
+    // Output premultiplied alpha.
+    return apply_opacity(vec4<f32>(vec3<f32>(p.r, p.g, p.b) * p.a, p.a), in.copy);
+}
@@ -0,0 +1,51 @@
+struct VertexInput {
+    @location(0) pos: vec2<f32>,
+    @location(1) glyph_pos: vec2<f32>,
+    @location(2) glyph_size: vec2<f32>,
+    @location(3) uv_min: vec2<f32>,
+    @location(4) uv_max: vec2<f32>
+}
+
+struct VertexOutput {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) uv: vec2<f32>
+}
+
+struct Uniforms {
+    aspect_ratio: f32,
+
+    x: f32,
+    y: f32,
+    size: f32,
+    offset: f32,
+    r: f32,
+    g: f32,
+    b: f32,
+    a: f32
+}
+
+@group(0) @binding(0) var<uniform> params: Uniforms;
+@group(0) @binding(1) var atlas: texture_2d<f32>;
+@group(0) @binding(2) var atlas_sampler: sampler;
+
+@vertex fn vs_main(
+    vertex: VertexInput
+) -> VertexOutput {
+    var pos = (vertex.glyph_pos + vertex.pos * vertex.glyph_size + vec2<f32>(params.offset, 0.0)) * params.size;
+    pos.y *= params.aspect_ratio;
+    pos += vec2<f32>(params.x, params.y);
+    var out: VertexOutput;
+    out.pos = vec4<f32>(apply_transform(pos, 0u), 0.0, 1.0);
+    // The atlas is stored top row first.
+    out.uv = vec2<f32>(
+        mix(vertex.uv_min.x, vertex.uv_max.x, vertex.pos.x),
+        mix(vertex.uv_max.y, vertex.uv_min.y, vertex.pos.y)
+    );
+    return out;
+}
+
+@fragment fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let alpha = params.a * textureSample(atlas, atlas_sampler, in.uv).r;
+    // Output premultiplied alpha.
+    return apply_opacity(vec4<f32>(vec3<f32>(params.r, params.g, params.b) * alpha, alpha), 0u);
+}
@@ -0,0 +1,26 @@
+// Shared by every object: moves, rotates and scales the object, and fades it with the groups that it is in.
+// Each copy of a repeated object has its own transform. Objects that can't be repeated only use copy 0.
+
+struct Transform {
+    // Columns of the matrix, which is applied with the same units on both axes so that shapes aren't skewed.
+    x_axis: vec2<f32>,
+    y_axis: vec2<f32>,
+    translation: vec2<f32>,
+    aspect_ratio: f32,
+    opacity: f32
+}
+
+@group(1) @binding(0) var<storage, read> transforms: array<Transform>;
+
+// Transform a position in screen space.
+fn apply_transform(pos: vec2<f32>, copy: u32) -> vec2<f32> {
+    let transform = transforms[copy];
+    var p = vec2<f32>(pos.x, pos.y / transform.aspect_ratio);
+    p = transform.x_axis * p.x + transform.y_axis * p.y + transform.translation;
+    return vec2<f32>(p.x, p.y * transform.aspect_ratio);
+}
+
+// Fade a colour with premultiplied alpha.
+fn apply_opacity(colour: vec4<f32>, copy: u32) -> vec4<f32> {
+    return colour * transforms[copy].opacity;
+}
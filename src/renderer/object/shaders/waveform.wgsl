@@ -0,0 +1,29 @@
+struct VertexInput {
+    @location(0) pos: vec2<f32>
+}
+
+struct VertexOutput {
+    @builtin(position) pos: vec4<f32>
+}
+
+struct Uniforms {
+    r: f32,
+    g: f32,
+    b: f32,
+    a: f32
+}
+
+@group(0) @binding(0) var<uniform> params: Uniforms;
+
+@vertex fn vs_main(
+    vertex: VertexInput
+) -> VertexOutput {
+    var out: VertexOutput;
+    out.pos = vec4<f32>(apply_transform(vertex.pos, 0u), 0.0, 1.0);
+    return out;
+}
+
+@fragment fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Output premultiplied alpha.
+    return apply_opacity(vec4<f32>(vec3<f32>(params.r, params.g, params.b) * params.a, params.a), 0u);
+}
\ No newline at end of file
@@ -0,0 +1,543 @@
+use std::f32::consts::PI;
+
+use bytemuck::{Zeroable, Pod};
+
+use crate::renderer::{Renderer, RenderPass, BlendMode};
+use super::{ObjectType, Bounds, transform};
+
+/// The number of segments in a full circle, for round joins and caps.
+const ROUND_SEGMENTS: f32 = 32.0;
+/// How far a mitred corner can stick out, relative to half the line width, before it is bevelled instead.
+const MITER_LIMIT: f32 = 4.0;
+/// Points closer together than this are treated as the same point.
+const MIN_DISTANCE: f32 = 1e-6;
+
+#[derive(Zeroable, Pod, Clone, Copy)]
+#[repr(C)]
+struct Vertex {
+    pos: [f32; 2]
+}
+
+/// How the corners of a line are drawn, where two of its segments meet.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, strum::EnumString, strum::VariantNames)]
+#[strum(serialize_all = "lowercase")]
+pub enum Join {
+    #[default]
+    #[strum(ascii_case_insensitive)]
+    Miter,
+    #[strum(ascii_case_insensitive)]
+    Round,
+    #[strum(ascii_case_insensitive)]
+    Bevel
+}
+
+/// How the ends of a line are drawn.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, strum::EnumString, strum::VariantNames)]
+#[strum(serialize_all = "lowercase")]
+pub enum Cap {
+    /// The line stops at its end points.
+    #[default]
+    #[strum(ascii_case_insensitive)]
+    Butt,
+    #[strum(ascii_case_insensitive)]
+    Round,
+    /// The line carries on past its end points by half of its width.
+    #[strum(ascii_case_insensitive)]
+    Square
+}
+
+/// How to draw a line along a list of points.
+pub struct Stroke {
+    pub width: f32,
+    pub join: Join,
+    pub cap: Cap
+}
+
+/// Triangles in a single colour, which are built on the CPU every frame.
+/// Used by the objects that are made of lines and flat shapes.
+pub struct Shape {
+    pipeline: wgpu::RenderPipeline,
+    mesh: Mesh,
+    vertex_buffer: wgpu::Buffer,
+    uniform_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup
+}
+
+impl Shape {
+    pub fn create_pipeline(device: &wgpu::Device, transform_layout: &wgpu::BindGroupLayout, blend: BlendMode) -> wgpu::RenderPipeline {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                    ty: wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Uniform, has_dynamic_offset: false, min_binding_size: None },
+                    count: None
+                }
+            ]
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[&bind_group_layout, transform_layout],
+            push_constant_ranges: &[]
+        });
+        let shader_module = transform::create_shader_module(device, "shape", include_str!("shaders/shape.wgsl"));
+        let shape_desc = wgpu::RenderPipelineDescriptor {
+            label: Some("shape"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader_module,
+                entry_point: Some("vs_main"),
+                buffers: &[
+                    wgpu::VertexBufferLayout {
+                        array_stride: std::mem::size_of::<Vertex>() as u64,
+                        step_mode: wgpu::VertexStepMode::Vertex,
+                        attributes: &[
+                            wgpu::VertexAttribute {
+                                format: wgpu::VertexFormat::Float32x2,
+                                offset: 0,
+                                shader_location: 0
+                            }
+                        ]
+                    }
+                ],
+                compilation_options: Default::default()
+            },
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                .. Default::default()
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader_module,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: wgpu::TextureFormat::Bgra8UnormSrgb,
+                    blend: Some(blend.blend_state()),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default()
+            }),
+            multiview: None,
+            cache: None
+        };
+        device.create_render_pipeline(&shape_desc)
+    }
+
+    /// Create an empty shape, using the pipeline of the object that draws it.
+    pub fn new(object_type: ObjectType, blend: BlendMode, renderer: &Renderer) -> Self {
+        let uniform_buffer = renderer.device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: (std::mem::size_of::<f32>() as u64) * 4,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false
+        });
+        let pipeline = renderer.get_render_pipeline(object_type, blend);
+        let bind_group_layout = pipeline.get_bind_group_layout(0);
+        let bind_group = renderer.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding()
+                }
+            ]
+        });
+        Self {
+            pipeline,
+            mesh: Mesh::default(),
+            vertex_buffer: Self::create_vertex_buffer(renderer, 0),
+            uniform_buffer,
+            bind_group
+        }
+    }
+
+    fn create_vertex_buffer(renderer: &Renderer, vertex_count: usize) -> wgpu::Buffer {
+        renderer.device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: (std::mem::size_of::<Vertex>() * vertex_count) as u64,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false
+        })
+    }
+
+    /// Remove the triangles from the last frame.
+    pub fn clear(&mut self) {
+        self.mesh.vertices.clear();
+    }
+
+    /// Add a line through the points. A closed line joins the last point back to the first, and has no caps.
+    pub fn stroke(&mut self, points: &[[f32; 2]], closed: bool, stroke: &Stroke) {
+        self.mesh.stroke(points, closed, stroke);
+    }
+
+    /// The triangles that will be written next.
+    pub(super) fn mesh(&mut self) -> &mut Mesh {
+        &mut self.mesh
+    }
+
+    /// Upload the triangles and colour to the GPU. Returns the area that the shape covers.
+    pub fn write(&mut self, renderer: &Renderer, aspect_ratio: f32, colour: [f32; 4], origin: [f32; 2]) -> Bounds {
+        let vertices = &mut self.mesh.vertices;
+        let mut min = [f32::INFINITY; 2];
+        let mut max = [f32::NEG_INFINITY; 2];
+        for vertex in vertices.iter_mut() {
+            vertex.pos[1] *= aspect_ratio;
+            for axis in 0..2 {
+                min[axis] = min[axis].min(vertex.pos[axis]);
+                max[axis] = max[axis].max(vertex.pos[axis]);
+            }
+        }
+
+        // The buffer grows when the shape needs more triangles than it has room for.
+        let size = (std::mem::size_of::<Vertex>() * vertices.len()) as u64;
+        if size > self.vertex_buffer.size() {
+            self.vertex_buffer = Self::create_vertex_buffer(renderer, vertices.len().next_power_of_two());
+        }
+        renderer.queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(vertices));
+        renderer.queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&colour));
+
+        if vertices.is_empty() {
+            return Bounds { origin, min: origin, size: [0.0, 0.0] };
+        }
+        Bounds {
+            origin,
+            min,
+            size: [max[0] - min[0], max[1] - min[1]]
+        }
+    }
+
+    pub fn draw(&self, render_pass: &mut RenderPass<'_>) {
+        if self.mesh.vertices.is_empty() {
+            return;
+        }
+        let render_pass = render_pass.render_pass.as_mut().unwrap();
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.draw(0..(self.mesh.vertices.len() as u32), 0..1);
+    }
+}
+
+/// The triangles of a shape, before they are uploaded to the GPU.
+#[derive(Default)]
+pub(super) struct Mesh {
+    /// Points with the y axis scaled so that both axes have the same units, until they are written.
+    vertices: Vec<Vertex>
+}
+
+impl Mesh {
+    /// Add a filled convex polygon. The points are in order around the centre.
+    pub(super) fn fill(&mut self, centre: [f32; 2], points: &[[f32; 2]]) {
+        for (i, &point) in points.iter().enumerate() {
+            self.triangle(centre, point, points[(i + 1) % points.len()]);
+        }
+    }
+
+    /// Add a line through the points. A closed line joins the last point back to the first, and has no caps.
+    pub(super) fn stroke(&mut self, points: &[[f32; 2]], closed: bool, stroke: &Stroke) {
+        let half_width = stroke.width * 0.5;
+        if half_width <= 0.0 {
+            return;
+        }
+
+        // Points that are on top of each other don't have a direction between them.
+        let mut points = points.to_vec();
+        points.dedup_by(|b, a| distance(*a, *b) < MIN_DISTANCE);
+        if closed && points.len() > 1 && distance(points[0], points[points.len() - 1]) < MIN_DISTANCE {
+            points.pop();
+        }
+        let closed = closed && points.len() > 2;
+        if points.len() < 2 {
+            // A line with no length is only drawn if it has caps that go past its ends.
+            if let Some(&point) = points.first() {
+                let [back_right, back_left] = self.cap(point, [-1.0, 0.0], half_width, stroke.cap);
+                let [left, right] = self.cap(point, [1.0, 0.0], half_width, stroke.cap);
+                self.triangle(back_left, back_right, left);
+                self.triangle(back_right, right, left);
+            }
+            return;
+        }
+
+        let segment_count = if closed { points.len() } else { points.len() - 1 };
+        let segments = (0..segment_count)
+            .map(|i| {
+                let (start, end) = (points[i], points[(i + 1) % points.len()]);
+                let length = distance(start, end);
+                ([(end[0] - start[0]) / length, (end[1] - start[1]) / length], length)
+            })
+            .collect::<Vec<_>>();
+
+        // The left and right corners at the start and end of each segment.
+        let mut starts = vec![[[0.0; 2]; 2]; segment_count];
+        let mut ends = vec![[[0.0; 2]; 2]; segment_count];
+        for (i, &point) in points.iter().enumerate() {
+            let incoming = if i > 0 { Some(i - 1) } else if closed { Some(segment_count - 1) } else { None };
+            let outgoing = (i < segment_count).then_some(i);
+            match (incoming, outgoing) {
+                (Some(incoming), Some(outgoing)) => {
+                    let (start, end) = self.join(point, segments[incoming], segments[outgoing], half_width, stroke.join);
+                    ends[incoming] = end;
+                    starts[outgoing] = start;
+                },
+                (None, Some(outgoing)) => {
+                    let direction = segments[outgoing].0;
+                    let back = [-direction[0], -direction[1]];
+                    starts[outgoing] = self.cap(point, back, half_width, stroke.cap);
+                    starts[outgoing].reverse();
+                },
+                (Some(incoming), None) => ends[incoming] = self.cap(point, segments[incoming].0, half_width, stroke.cap),
+                (None, None) => {}
+            }
+        }
+
+        for (start, end) in starts.into_iter().zip(ends) {
+            self.triangle(start[0], start[1], end[0]);
+            self.triangle(start[1], end[1], end[0]);
+        }
+    }
+
+    /// Add the cap at the end of a line that goes in `direction`.
+    /// Returns the left and right corners where the line meets the cap.
+    fn cap(&mut self, point: [f32; 2], direction: [f32; 2], half_width: f32, cap: Cap) -> [[f32; 2]; 2] {
+        let normal = scale(perpendicular(direction), half_width);
+        let end = match cap {
+            Cap::Square => add(point, scale(direction, half_width)),
+            Cap::Butt | Cap::Round => point
+        };
+        let right = sub(end, normal);
+        if cap == Cap::Round {
+            self.arc(point, point, right, PI);
+        }
+        [add(end, normal), right]
+    }
+
+    /// Add the corner where two segments meet, on the outside of the turn.
+    /// Returns the corners at the start of the outgoing segment and at the end of the incoming segment.
+    fn join(&mut self, point: [f32; 2], incoming: ([f32; 2], f32), outgoing: ([f32; 2], f32), half_width: f32, join: Join) -> ([[f32; 2]; 2], [[f32; 2]; 2]) {
+        let ((d0, length0), (d1, length1)) = (incoming, outgoing);
+        let (n0, n1) = (perpendicular(d0), perpendicular(d1));
+        let turn = cross(d0, d1);
+        if turn.abs() < MIN_DISTANCE && dot(d0, d1) > 0.0 {
+            // The segments carry on in a straight line.
+            let corners = [add(point, scale(n0, half_width)), sub(point, scale(n0, half_width))];
+            return (corners, corners);
+        }
+
+        // The outside of the corner is on the right if the line turns left.
+        let side = if turn > 0.0 { -half_width } else { half_width };
+        let outer0 = add(point, scale(n0, side));
+        let outer1 = add(point, scale(n1, side));
+
+        // The inner edges of the segments meet at one point, unless the segments are too short to reach it.
+        // Both segments end there so that they don't overlap, which would show if the line is transparent.
+        let bisector = normalize(add(n0, n1));
+        // This is infinite if the line turns back on itself.
+        let miter = 1.0 / dot(bisector, n0);
+        let inner_distance = (half_width * miter * dot(bisector, d0)).abs();
+        let (inner0, inner1, centre) = if inner_distance <= length0.min(length1) {
+            let inner = sub(point, scale(bisector, side * miter));
+            (inner, inner, inner)
+        } else {
+            (sub(point, scale(n0, side)), sub(point, scale(n1, side)), point)
+        };
+
+        match join {
+            Join::Miter if miter <= MITER_LIMIT => {
+                let tip = add(point, scale(bisector, side * miter));
+                self.triangle(centre, outer0, tip);
+                self.triangle(centre, tip, outer1);
+            },
+            Join::Round => {
+                let angle = dot(d0, d1).clamp(-1.0, 1.0).acos();
+                self.arc(centre, point, outer0, if turn > 0.0 { angle } else { -angle });
+            },
+            Join::Miter | Join::Bevel => self.triangle(centre, outer0, outer1)
+        }
+
+        // Left corners come first.
+        if turn > 0.0 {
+            ([inner1, outer1], [inner0, outer0])
+        } else {
+            ([outer1, inner1], [outer0, inner0])
+        }
+    }
+
+    /// Add a fan of triangles from `centre` to an arc around `point`, which starts at `start` and turns anticlockwise by `angle`.
+    fn arc(&mut self, centre: [f32; 2], point: [f32; 2], start: [f32; 2], angle: f32) {
+        let steps = (angle.abs() * ROUND_SEGMENTS / (2.0 * PI)).ceil().max(1.0);
+        let offset = sub(start, point);
+        let mut previous = start;
+        for step in 1..=(steps as usize) {
+            let (sin, cos) = (angle * step as f32 / steps).sin_cos();
+            let next = add(point, [offset[0] * cos - offset[1] * sin, offset[0] * sin + offset[1] * cos]);
+            self.triangle(centre, previous, next);
+            previous = next;
+        }
+    }
+
+    fn triangle(&mut self, a: [f32; 2], b: [f32; 2], c: [f32; 2]) {
+        self.vertices.extend([Vertex { pos: a }, Vertex { pos: b }, Vertex { pos: c }]);
+    }
+}
+
+fn add(a: [f32; 2], b: [f32; 2]) -> [f32; 2] {
+    [a[0] + b[0], a[1] + b[1]]
+}
+
+fn sub(a: [f32; 2], b: [f32; 2]) -> [f32; 2] {
+    [a[0] - b[0], a[1] - b[1]]
+}
+
+fn scale(a: [f32; 2], s: f32) -> [f32; 2] {
+    [a[0] * s, a[1] * s]
+}
+
+fn dot(a: [f32; 2], b: [f32; 2]) -> f32 {
+    a[0] * b[0] + a[1] * b[1]
+}
+
+fn cross(a: [f32; 2], b: [f32; 2]) -> f32 {
+    a[0] * b[1] - a[1] * b[0]
+}
+
+fn distance(a: [f32; 2], b: [f32; 2]) -> f32 {
+    let d = sub(b, a);
+    dot(d, d).sqrt()
+}
+
+/// Turn a direction anticlockwise by a right angle.
+fn perpendicular(a: [f32; 2]) -> [f32; 2] {
+    [-a[1], a[0]]
+}
+
+fn normalize(a: [f32; 2]) -> [f32; 2] {
+    scale(a, 1.0 / dot(a, a).sqrt().max(MIN_DISTANCE))
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+
+    const JOINS: [Join; 3] = [Join::Miter, Join::Round, Join::Bevel];
+    const CAPS: [Cap; 3] = [Cap::Butt, Cap::Round, Cap::Square];
+
+    fn stroke(points: &[[f32; 2]], closed: bool, width: f32, join: Join, cap: Cap) -> Vec<[f32; 2]> {
+        let mut mesh = Mesh::default();
+        mesh.stroke(points, closed, &Stroke { width, join, cap });
+        vertices(&mesh)
+    }
+
+    /// The corners of the triangles in the mesh.
+    pub(in crate::renderer::object) fn vertices(mesh: &Mesh) -> Vec<[f32; 2]> {
+        assert_eq!(mesh.vertices.len() % 3, 0);
+        mesh.vertices.iter().map(|vertex| vertex.pos).collect()
+    }
+
+    /// The total area of the triangles. This is the area of the line if none of the triangles overlap.
+    pub(in crate::renderer::object) fn area(vertices: &[[f32; 2]]) -> f32 {
+        vertices.chunks_exact(3)
+            .map(|t| cross(sub(t[1], t[0]), sub(t[2], t[0])).abs() * 0.5)
+            .sum()
+    }
+
+    pub(in crate::renderer::object) fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-3, "{a} != {b}");
+    }
+
+    #[test]
+    fn nothing_to_draw() {
+        for (join, cap) in JOINS.into_iter().zip(CAPS) {
+            assert!(stroke(&[], false, 0.1, join, cap).is_empty());
+            assert!(stroke(&[], true, 0.1, join, cap).is_empty());
+            assert!(stroke(&[[0.0, 0.0], [1.0, 0.0]], false, 0.0, join, cap).is_empty());
+            assert!(stroke(&[[0.0, 0.0], [1.0, 0.0]], false, -1.0, join, cap).is_empty());
+        }
+    }
+
+    #[test]
+    fn single_point_is_drawn_by_its_caps() {
+        let point = [[0.5, 0.5]];
+        let same_point = [[0.5, 0.5], [0.5, 0.5 + MIN_DISTANCE * 0.1], [0.5, 0.5]];
+        for points in [&point[..], &same_point[..]] {
+            for closed in [false, true] {
+                assert_close(area(&stroke(points, closed, 0.2, Join::Miter, Cap::Butt)), 0.0);
+                assert_close(area(&stroke(points, closed, 0.2, Join::Miter, Cap::Square)), 0.04);
+                assert_close(area(&stroke(points, closed, 0.2, Join::Miter, Cap::Round)), PI * 0.01);
+            }
+        }
+    }
+
+    #[test]
+    fn caps_extend_straight_lines() {
+        let points = [[0.0, 0.0], [0.5, 0.0], [1.0, 0.0]];
+        for join in JOINS {
+            assert_close(area(&stroke(&points, false, 0.2, join, Cap::Butt)), 0.2);
+            assert_close(area(&stroke(&points, false, 0.2, join, Cap::Square)), 0.24);
+            assert_close(area(&stroke(&points, false, 0.2, join, Cap::Round)), 0.2 + PI * 0.01);
+        }
+    }
+
+    #[test]
+    fn repeated_points_are_ignored() {
+        let points = [[0.0, 0.0], [0.0, 0.0], [1.0, 0.0], [1.0, 0.0], [1.0, 1.0], [1.0, 1.0]];
+        let unique = [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0]];
+        for (join, cap) in JOINS.into_iter().zip(CAPS) {
+            assert_eq!(stroke(&points, false, 0.2, join, cap), stroke(&unique, false, 0.2, join, cap));
+        }
+    }
+
+    #[test]
+    fn right_angle_joins() {
+        let points = [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0]];
+        let corner = 0.1 * 0.1;
+        assert_close(area(&stroke(&points, false, 0.2, Join::Miter, Cap::Butt)), 0.4);
+        assert_close(area(&stroke(&points, false, 0.2, Join::Bevel, Cap::Butt)), 0.4 - corner * 0.5);
+        assert_close(area(&stroke(&points, false, 0.2, Join::Round, Cap::Butt)), 0.4 - corner + PI * corner * 0.25);
+    }
+
+    #[test]
+    fn closed_lines() {
+        let square = [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]];
+        let mut repeated_end = square.to_vec();
+        repeated_end.push(square[0]);
+        for cap in CAPS {
+            assert_close(area(&stroke(&square, true, 0.2, Join::Miter, cap)), 0.8);
+            assert_eq!(stroke(&repeated_end, true, 0.2, Join::Miter, cap), stroke(&square, true, 0.2, Join::Miter, cap));
+        }
+        // Two points can't be closed, so they are drawn as an open line with caps.
+        let pair = [[0.0, 0.0], [1.0, 0.0]];
+        assert_eq!(stroke(&pair, true, 0.2, Join::Miter, Cap::Square), stroke(&pair, false, 0.2, Join::Miter, Cap::Square));
+    }
+
+    #[test]
+    fn degenerate_corners_are_finite() {
+        let lines: [&[[f32; 2]]; 4] = [
+            // Turns back on itself.
+            &[[0.0, 0.0], [1.0, 0.0], [0.0, 0.0]],
+            &[[0.0, 0.0], [1.0, 0.0], [0.5, 0.0], [2.0, 0.0]],
+            // Segments much shorter than the line is wide.
+            &[[0.0, 0.0], [1e-4, 0.0], [1e-4, 1e-4], [2e-4, 1e-4]],
+            // A very sharp turn, which is too long to mitre.
+            &[[0.0, 0.0], [1.0, 0.0], [0.0, 1e-3]],
+        ];
+        for points in lines {
+            for closed in [false, true] {
+                for (join, cap) in JOINS.into_iter().flat_map(|join| CAPS.map(|cap| (join, cap))) {
+                    let vertices = stroke(points, closed, 0.2, join, cap);
+                    assert!(!vertices.is_empty());
+                    assert!(vertices.iter().flatten().all(|n| n.is_finite()), "{points:?} {join:?} {cap:?}");
+                    // Nothing reaches further from the line than a mitre could.
+                    let reach = 0.1 * MITER_LIMIT + 1.0;
+                    assert!(vertices.iter().flatten().all(|n| n.abs() <= 2.0 + reach), "{points:?} {join:?} {cap:?}");
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,291 @@
+use bytemuck::{Zeroable, Pod};
+use wgpu::util::DeviceExt;
+
+use crate::{
+    audio::{AudioPacket, Channel}, operation::Operation, renderer::{
+        Renderer, RenderPass, RenderParam, Mapping, CreationError, BlendMode
+    }
+};
+use super::{ObjectRenderable, ObjectType, Bounds, transform};
+
+const VERTEX_COUNT: usize = 4;
+const MAX_BARS: usize = 1024;
+
+#[derive(Zeroable, Pod, Clone, Copy)]
+#[repr(C)]
+struct Vertex {
+    pos: [f32; 2]
+}
+
+#[derive(Zeroable, Pod, Clone, Copy)]
+#[repr(C)]
+struct Instance {
+    /// Bottom-left corner of the bar.
+    pos: [f32; 2],
+    size: [f32; 2]
+}
+
+/// Bars showing the spectrum of the current frame.
+pub struct SpectrumRenderable {
+    params: SpectrumParameters,
+    pipeline: wgpu::RenderPipeline,
+    vertex_buffer: wgpu::Buffer,
+    instances: Vec<Instance>,
+    instance_buffer: wgpu::Buffer,
+    uniform_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup
+}
+
+/// The parameters of a spectrum, taken from its mapping.
+pub struct SpectrumParameters {
+    x: Operation,
+    y: Operation,
+    width: Operation,
+    height: Operation,
+    bars: Operation,
+    gap: Operation,
+    min_freq: Operation,
+    max_freq: Operation,
+    log: Operation,
+    db_floor: Operation,
+    db_ceiling: Operation,
+    mirror: Operation,
+    r: Operation,
+    g: Operation,
+    b: Operation,
+    a: Operation,
+    channel: Channel,
+}
+
+impl SpectrumParameters {
+    pub fn new(mapping: &mut Mapping) -> Self {
+        let freq_span = mapping.span(RenderParam::MinFreq).or(mapping.span(RenderParam::MaxFreq));
+        let params = Self {
+            x: mapping.get(RenderParam::X),
+            y: mapping.get(RenderParam::Y),
+            width: mapping.get(RenderParam::Width),
+            height: mapping.get(RenderParam::Height),
+            bars: mapping.get(RenderParam::Bars),
+            gap: mapping.get_or(RenderParam::Gap, 0.2),
+            min_freq: mapping.get_or(RenderParam::MinFreq, 20.0),
+            max_freq: mapping.get_or(RenderParam::MaxFreq, 20000.0),
+            log: mapping.get_or(RenderParam::Log, 1.0),
+            db_floor: mapping.get_or(RenderParam::DbFloor, -60.0),
+            db_ceiling: mapping.get_or(RenderParam::DbCeiling, 0.0),
+            mirror: mapping.get_or(RenderParam::Mirror, 0.0),
+            r: mapping.get(RenderParam::R),
+            g: mapping.get(RenderParam::G),
+            b: mapping.get(RenderParam::B),
+            a: mapping.get_or(RenderParam::A, 1.0),
+            channel: mapping.take_channel(),
+        };
+        // Ranges that change over time are checked when the spectrum is drawn.
+        if let (Some(lo), Some(hi)) = (params.min_freq.constant_value(), params.max_freq.constant_value()) && lo >= hi {
+            mapping.error(CreationError::InvalidRange(RenderParam::MinFreq, RenderParam::MaxFreq, freq_span));
+        }
+        params
+    }
+}
+
+impl SpectrumRenderable {
+    pub fn create_pipeline(device: &wgpu::Device, transform_layout: &wgpu::BindGroupLayout, blend: BlendMode) -> wgpu::RenderPipeline {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                    ty: wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Uniform, has_dynamic_offset: false, min_binding_size: None },
+                    count: None
+                }
+            ]
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[&bind_group_layout, transform_layout],
+            push_constant_ranges: &[]
+        });
+        let shader_module = transform::create_shader_module(device, "spectrum", include_str!("shaders/spectrum.wgsl"));
+        let spectrum_desc = wgpu::RenderPipelineDescriptor {
+            label: Some("spectrum"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader_module,
+                entry_point: Some("vs_main"),
+                buffers: &[
+                    wgpu::VertexBufferLayout {
+                        array_stride: std::mem::size_of::<Vertex>() as u64,
+                        step_mode: wgpu::VertexStepMode::Vertex,
+                        attributes: &[
+                            wgpu::VertexAttribute {
+                                format: wgpu::VertexFormat::Float32x2,
+                                offset: 0,
+                                shader_location: 0
+                            }
+                        ]
+                    },
+                    wgpu::VertexBufferLayout {
+                        array_stride: std::mem::size_of::<Instance>() as u64,
+                        step_mode: wgpu::VertexStepMode::Instance,
+                        attributes: &[
+                            wgpu::VertexAttribute {
+                                format: wgpu::VertexFormat::Float32x2,
+                                offset: 0,
+                                shader_location: 1
+                            },
+                            wgpu::VertexAttribute {
+                                format: wgpu::VertexFormat::Float32x2,
+                                offset: std::mem::size_of::<[f32; 2]>() as u64,
+                                shader_location: 2
+                            }
+                        ]
+                    }
+                ],
+                compilation_options: Default::default()
+            },
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleStrip,
+                .. Default::default()
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader_module,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: wgpu::TextureFormat::Bgra8UnormSrgb,
+                    blend: Some(blend.blend_state()),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default()
+            }),
+            multiview: None,
+            cache: None
+        };
+        device.create_render_pipeline(&spectrum_desc)
+    }
+
+    /// Create a new spectrum to display on-screen.
+    pub fn new(params: SpectrumParameters, blend: BlendMode, renderer: &Renderer) -> Self {
+        let buf = [
+            Vertex{pos: [0.0, 0.0]},
+            Vertex{pos: [1.0, 0.0]},
+            Vertex{pos: [0.0, 1.0]},
+            Vertex{pos: [1.0, 1.0]}
+        ];
+        let vertex_buffer = renderer.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: None,
+            usage: wgpu::BufferUsages::VERTEX,
+            contents: bytemuck::cast_slice(&buf)
+        });
+        let instance_buffer = renderer.device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: (std::mem::size_of::<Instance>() * MAX_BARS) as u64,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false
+        });
+        let uniform_buffer = renderer.device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: (std::mem::size_of::<f32>() as u64) * 4,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false
+        });
+        let pipeline = renderer.get_render_pipeline(ObjectType::Spectrum, blend);
+        let bind_group_layout = pipeline.get_bind_group_layout(0);
+        let bind_group = renderer.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding()
+                }
+            ]
+        });
+        Self {
+            params,
+            pipeline,
+            vertex_buffer,
+            instances: Vec::with_capacity(MAX_BARS),
+            instance_buffer,
+            uniform_buffer,
+            bind_group
+        }
+    }
+}
+
+impl ObjectRenderable for SpectrumRenderable {
+    fn update(&mut self, renderer: &Renderer, audio_packet: &AudioPacket, _aspect_ratio: f32, bounds: &mut Vec<Bounds>) {
+        let x = self.params.x.eval(audio_packet);
+        let y = self.params.y.eval(audio_packet);
+        let width = self.params.width.eval(audio_packet);
+        let height = self.params.height.eval(audio_packet);
+        let mut bars = (self.params.bars.eval(audio_packet).round().max(0.0) as usize).min(MAX_BARS);
+        let gap = self.params.gap.eval(audio_packet).clamp(0.0, 1.0);
+        let min_freq = self.params.min_freq.eval(audio_packet);
+        let max_freq = self.params.max_freq.eval(audio_packet);
+        let log = self.params.log.eval(audio_packet) > 0.0;
+        let db_floor = self.params.db_floor.eval(audio_packet);
+        let db_ceiling = self.params.db_ceiling.eval(audio_packet);
+        let mirror = self.params.mirror.eval(audio_packet) > 0.0;
+        // There are no bars while the range of frequencies is empty.
+        if min_freq >= max_freq || min_freq.is_nan() || max_freq.is_nan() {
+            bars = 0;
+        }
+
+        // Get the frequency at a position along the x axis, from 0.0 to 1.0.
+        let frequency = |t: f32| if log {
+            min_freq.max(1.0) * (max_freq / min_freq.max(1.0)).powf(t)
+        } else {
+            min_freq + (max_freq - min_freq) * t
+        };
+
+        let spectrum = audio_packet.get_spectrum(self.params.channel);
+        let slot_width = width / (bars.max(1) as f32);
+        self.instances.clear();
+        self.instances.extend((0..bars).map(|i| {
+            let lo = frequency((i as f32) / (bars as f32));
+            let hi = frequency(((i + 1) as f32) / (bars as f32));
+            let db = 20.0 * spectrum.peak(lo, hi).max(f32::MIN_POSITIVE).log10();
+            let level = ((db - db_floor) / (db_ceiling - db_floor).max(f32::EPSILON)).clamp(0.0, 1.0);
+
+            let bar_x = x + slot_width * ((i as f32) + gap * 0.5);
+            let bar_width = slot_width * (1.0 - gap);
+            if mirror {
+                // Grow outwards from the centre line.
+                let bar_height = level * height;
+                Instance { pos: [bar_x, y + (height - bar_height) * 0.5], size: [bar_width, bar_height] }
+            } else {
+                Instance { pos: [bar_x, y], size: [bar_width, level * height] }
+            }
+        }));
+        renderer.queue.write_buffer(&self.instance_buffer, 0, bytemuck::cast_slice(&self.instances));
+
+        let uniform_data = [
+            self.params.r.eval(audio_packet),
+            self.params.g.eval(audio_packet),
+            self.params.b.eval(audio_packet),
+            self.params.a.eval(audio_packet)
+        ];
+        renderer.queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&uniform_data));
+        bounds.push(Bounds {
+            origin: [x, y],
+            min: [x, y],
+            size: [width, height]
+        });
+    }
+
+    fn draw(&self, render_pass: &mut RenderPass<'_>) {
+        let render_pass = render_pass.render_pass.as_mut().unwrap();
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
+        let max = VERTEX_COUNT as u32;
+        render_pass.draw(0..max, 0..(self.instances.len() as u32));
+    }
+}
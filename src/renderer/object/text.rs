@@ -0,0 +1,392 @@
+use std::{collections::HashMap, rc::Rc};
+
+use bytemuck::{Zeroable, Pod};
+use wgpu::util::DeviceExt;
+
+use crate::{
+    audio::AudioPacket, operation::{Operation, TextTemplate}, renderer::{
+        Renderer, RenderPass, RenderParam, Mapping, BlendMode
+    }
+};
+use super::{ObjectRenderable, ObjectType, Bounds, transform};
+
+const VERTEX_COUNT: usize = 4;
+/// The most characters that a text object can draw, not counting spaces.
+pub const MAX_GLYPHS: usize = 256;
+
+const FONT_DATA: &[u8] = include_bytes!("../../../assets/fonts/DejaVuSans.ttf");
+/// Size that glyphs are rasterized at, in pixels.
+const FONT_SIZE: f32 = 64.0;
+const ATLAS_WIDTH: usize = 1024;
+/// Space around each glyph in the atlas, so that glyphs don't bleed into each other when sampled.
+const PADDING: usize = 2;
+/// Shown in place of characters that aren't in the atlas.
+const REPLACEMENT: char = '?';
+
+#[derive(Zeroable, Pod, Clone, Copy)]
+#[repr(C)]
+struct Vertex {
+    pos: [f32; 2]
+}
+
+#[derive(Zeroable, Pod, Clone, Copy)]
+#[repr(C)]
+struct Instance {
+    /// Bottom-left corner of the glyph, relative to the start of the baseline.
+    pos: [f32; 2],
+    size: [f32; 2],
+    uv_min: [f32; 2],
+    uv_max: [f32; 2]
+}
+
+/// A glyph in the font atlas. Sizes are relative to the font size.
+struct Glyph {
+    advance: f32,
+    /// Bottom-left corner of the glyph, relative to the pen position on the baseline.
+    pos: [f32; 2],
+    size: [f32; 2],
+    uv_min: [f32; 2],
+    uv_max: [f32; 2]
+}
+
+/// A texture containing every glyph that text can be drawn with.
+/// This is shared by every text object.
+pub struct FontAtlas {
+    font: fontdue::Font,
+    glyphs: HashMap<char, Glyph>,
+    /// Height above and below the baseline, relative to the font size. `descent` is negative.
+    ascent: f32,
+    descent: f32,
+    view: wgpu::TextureView,
+    sampler: wgpu::Sampler
+}
+
+impl FontAtlas {
+    /// Rasterize the glyphs of the built-in font.
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        let font = fontdue::Font::from_bytes(FONT_DATA, fontdue::FontSettings::default())
+            .expect("built-in font is valid");
+
+        // ASCII, Latin-1 and common punctuation.
+        let characters = (' '..='~').chain('\u{a0}'..='\u{ff}').chain('\u{2010}'..='\u{2026}');
+        let rasterized = characters.map(|c| (c, font.rasterize(c, FONT_SIZE))).collect::<Vec<_>>();
+
+        // Pack the glyphs into rows.
+        let mut positions = Vec::with_capacity(rasterized.len());
+        let (mut x, mut y, mut row_height) = (PADDING, PADDING, 0);
+        for (_, (metrics, _)) in &rasterized {
+            if x + metrics.width + PADDING > ATLAS_WIDTH {
+                x = PADDING;
+                y += row_height + PADDING;
+                row_height = 0;
+            }
+            positions.push((x, y));
+            x += metrics.width + PADDING;
+            row_height = row_height.max(metrics.height);
+        }
+        let atlas_height = y + row_height + PADDING;
+
+        let mut data = vec![0_u8; ATLAS_WIDTH * atlas_height];
+        let mut glyphs = HashMap::new();
+        for ((c, (metrics, bitmap)), (x, y)) in rasterized.into_iter().zip(positions) {
+            for (row, pixels) in bitmap.chunks_exact(metrics.width.max(1)).enumerate() {
+                let start = (y + row) * ATLAS_WIDTH + x;
+                data[start..(start + metrics.width)].copy_from_slice(pixels);
+            }
+            glyphs.insert(c, Glyph {
+                advance: metrics.advance_width / FONT_SIZE,
+                pos: [(metrics.xmin as f32) / FONT_SIZE, (metrics.ymin as f32) / FONT_SIZE],
+                size: [(metrics.width as f32) / FONT_SIZE, (metrics.height as f32) / FONT_SIZE],
+                uv_min: [(x as f32) / (ATLAS_WIDTH as f32), (y as f32) / (atlas_height as f32)],
+                uv_max: [((x + metrics.width) as f32) / (ATLAS_WIDTH as f32), ((y + metrics.height) as f32) / (atlas_height as f32)]
+            });
+        }
+
+        let texture = device.create_texture_with_data(queue, &wgpu::TextureDescriptor {
+            label: Some("font atlas"),
+            size: wgpu::Extent3d {
+                width: ATLAS_WIDTH as u32, height: atlas_height as u32, depth_or_array_layers: 1
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::R8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[]
+        }, wgpu::util::TextureDataOrder::LayerMajor, &data);
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let line_metrics = font.horizontal_line_metrics(FONT_SIZE).expect("built-in font is horizontal");
+        Self {
+            font,
+            glyphs,
+            ascent: line_metrics.ascent / FONT_SIZE,
+            descent: line_metrics.descent / FONT_SIZE,
+            view: texture.create_view(&Default::default()),
+            sampler
+        }
+    }
+
+    /// Lay out a line of text, starting at the origin. Characters after the first `MAX_GLYPHS` are left out,
+    /// but still count towards the width. Returns the width of the text, relative to the font size.
+    fn layout(&self, text: &str, instances: &mut Vec<Instance>) -> f32 {
+        let mut pen = 0.0;
+        let mut previous = None;
+        for c in text.chars() {
+            let c = if self.glyphs.contains_key(&c) { c } else { REPLACEMENT };
+            let glyph = &self.glyphs[&c];
+            if let Some(previous) = previous {
+                pen += self.font.horizontal_kern(previous, c, FONT_SIZE).unwrap_or(0.0) / FONT_SIZE;
+            }
+            if glyph.size[0] > 0.0 && instances.len() < MAX_GLYPHS {
+                instances.push(Instance {
+                    pos: [pen + glyph.pos[0], glyph.pos[1]],
+                    size: glyph.size,
+                    uv_min: glyph.uv_min,
+                    uv_max: glyph.uv_max
+                });
+            }
+            pen += glyph.advance;
+            previous = Some(c);
+        }
+        pen
+    }
+}
+
+/// A line of text.
+pub struct TextRenderable {
+    params: TextParameters,
+    atlas: Rc<FontAtlas>,
+    pipeline: wgpu::RenderPipeline,
+    vertex_buffer: wgpu::Buffer,
+    instances: Vec<Instance>,
+    instance_buffer: wgpu::Buffer,
+    uniform_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup
+}
+
+/// The parameters of a text object, taken from its mapping.
+pub struct TextParameters {
+    text: TextTemplate,
+    x: Operation,
+    y: Operation,
+    size: Operation,
+    align: Operation,
+    r: Operation,
+    g: Operation,
+    b: Operation,
+    a: Operation,
+}
+
+impl TextParameters {
+    pub fn new(mapping: &mut Mapping) -> Self {
+        Self {
+            text: mapping.take_text(),
+            x: mapping.get(RenderParam::X),
+            y: mapping.get(RenderParam::Y),
+            size: mapping.get(RenderParam::Size),
+            align: mapping.get_or(RenderParam::Align, 0.0),
+            r: mapping.get(RenderParam::R),
+            g: mapping.get(RenderParam::G),
+            b: mapping.get(RenderParam::B),
+            a: mapping.get_or(RenderParam::A, 1.0),
+        }
+    }
+}
+
+impl TextRenderable {
+    pub fn create_pipeline(device: &wgpu::Device, transform_layout: &wgpu::BindGroupLayout, blend: BlendMode) -> wgpu::RenderPipeline {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                    ty: wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Uniform, has_dynamic_offset: false, min_binding_size: None },
+                    count: None
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false
+                    },
+                    count: None
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None
+                }
+            ]
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[&bind_group_layout, transform_layout],
+            push_constant_ranges: &[]
+        });
+        let shader_module = transform::create_shader_module(device, "text", include_str!("shaders/text.wgsl"));
+        let text_desc = wgpu::RenderPipelineDescriptor {
+            label: Some("text"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader_module,
+                entry_point: Some("vs_main"),
+                buffers: &[
+                    wgpu::VertexBufferLayout {
+                        array_stride: std::mem::size_of::<Vertex>() as u64,
+                        step_mode: wgpu::VertexStepMode::Vertex,
+                        attributes: &[
+                            wgpu::VertexAttribute {
+                                format: wgpu::VertexFormat::Float32x2,
+                                offset: 0,
+                                shader_location: 0
+                            }
+                        ]
+                    },
+                    wgpu::VertexBufferLayout {
+                        array_stride: std::mem::size_of::<Instance>() as u64,
+                        step_mode: wgpu::VertexStepMode::Instance,
+                        attributes: &wgpu::vertex_attr_array![
+                            1 => Float32x2,
+                            2 => Float32x2,
+                            3 => Float32x2,
+                            4 => Float32x2
+                        ]
+                    }
+                ],
+                compilation_options: Default::default()
+            },
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleStrip,
+                .. Default::default()
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader_module,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: wgpu::TextureFormat::Bgra8UnormSrgb,
+                    blend: Some(blend.blend_state()),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default()
+            }),
+            multiview: None,
+            cache: None
+        };
+        device.create_render_pipeline(&text_desc)
+    }
+
+    /// Create a new line of text to display on-screen.
+    pub fn new(params: TextParameters, blend: BlendMode, renderer: &Renderer) -> Self {
+        let buf = [
+            Vertex{pos: [0.0, 0.0]},
+            Vertex{pos: [1.0, 0.0]},
+            Vertex{pos: [0.0, 1.0]},
+            Vertex{pos: [1.0, 1.0]}
+        ];
+        let vertex_buffer = renderer.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: None,
+            usage: wgpu::BufferUsages::VERTEX,
+            contents: bytemuck::cast_slice(&buf)
+        });
+        let instance_buffer = renderer.device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: (std::mem::size_of::<Instance>() * MAX_GLYPHS) as u64,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false
+        });
+        let uniform_buffer = renderer.device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: (std::mem::size_of::<f32>() as u64) * 9,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false
+        });
+        let atlas = renderer.get_font_atlas();
+        let pipeline = renderer.get_render_pipeline(ObjectType::Text, blend);
+        let bind_group_layout = pipeline.get_bind_group_layout(0);
+        let bind_group = renderer.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding()
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&atlas.view)
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&atlas.sampler)
+                }
+            ]
+        });
+        Self {
+            params,
+            atlas,
+            pipeline,
+            vertex_buffer,
+            instances: Vec::with_capacity(MAX_GLYPHS),
+            instance_buffer,
+            uniform_buffer,
+            bind_group
+        }
+    }
+}
+
+impl ObjectRenderable for TextRenderable {
+    fn update(&mut self, renderer: &Renderer, audio_packet: &AudioPacket, aspect_ratio: f32, bounds: &mut Vec<Bounds>) {
+        let text = self.params.text.eval(audio_packet);
+        self.instances.clear();
+        let width = self.atlas.layout(text, &mut self.instances);
+        renderer.queue.write_buffer(&self.instance_buffer, 0, bytemuck::cast_slice(&self.instances));
+
+        let x = self.params.x.eval(audio_packet);
+        let y = self.params.y.eval(audio_packet);
+        let size = self.params.size.eval(audio_packet);
+        // Move the text so that the alignment point is at x.
+        let offset = -self.params.align.eval(audio_packet) * width;
+        let uniform_data = [
+            aspect_ratio,
+            x,
+            y,
+            size,
+            offset,
+            self.params.r.eval(audio_packet),
+            self.params.g.eval(audio_packet),
+            self.params.b.eval(audio_packet),
+            self.params.a.eval(audio_packet)
+        ];
+        renderer.queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&uniform_data));
+        bounds.push(Bounds {
+            origin: [x, y],
+            min: [x + offset * size, y + self.atlas.descent * size * aspect_ratio],
+            size: [width * size, (self.atlas.ascent - self.atlas.descent) * size * aspect_ratio]
+        });
+    }
+
+    fn draw(&self, render_pass: &mut RenderPass<'_>) {
+        let render_pass = render_pass.render_pass.as_mut().unwrap();
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
+        let max = VERTEX_COUNT as u32;
+        render_pass.draw(0..max, 0..(self.instances.len() as u32));
+    }
+}
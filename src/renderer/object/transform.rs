@@ -0,0 +1,270 @@
+use crate::{
+    audio::AudioPacket, operation::Operation, renderer::{
+        Renderer, RenderPass, RenderParam, Mapping
+    }
+};
+use super::ObjectRenderable;
+
+/// Shader code that every object's shader starts with. It provides `apply_transform`.
+const TRANSFORM_SHADER: &str = include_str!("shaders/transform.wgsl");
+
+/// The area that an object covers before it is transformed, in screen space.
+#[derive(Clone, Copy)]
+pub struct Bounds {
+    /// The position of the object, from its `x` and `y` parameters.
+    pub origin: [f32; 2],
+    /// Bottom-left corner.
+    pub min: [f32; 2],
+    pub size: [f32; 2]
+}
+
+/// A 2D affine transform.
+#[derive(Clone, Copy)]
+pub struct Affine {
+    /// Columns of the matrix.
+    matrix: [[f32; 2]; 2],
+    translation: [f32; 2]
+}
+
+impl Affine {
+    pub fn translate(x: f32, y: f32) -> Self {
+        Self {
+            matrix: [[1.0, 0.0], [0.0, 1.0]],
+            translation: [x, y]
+        }
+    }
+
+    pub fn rotate(angle: f32) -> Self {
+        let (s, c) = angle.sin_cos();
+        Self {
+            matrix: [[c, s], [-s, c]],
+            translation: [0.0, 0.0]
+        }
+    }
+
+    pub fn scale(x: f32, y: f32) -> Self {
+        Self {
+            matrix: [[x, 0.0], [0.0, y]],
+            translation: [0.0, 0.0]
+        }
+    }
+
+    pub fn identity() -> Self {
+        Self::translate(0.0, 0.0)
+    }
+
+    /// Apply this transform, and then `other`.
+    pub fn then(self, other: Affine) -> Self {
+        let translation = other.apply_vector(self.translation);
+        Self {
+            matrix: [other.apply_vector(self.matrix[0]), other.apply_vector(self.matrix[1])],
+            translation: [translation[0] + other.translation[0], translation[1] + other.translation[1]]
+        }
+    }
+
+    /// Apply the matrix without the translation.
+    fn apply_vector(&self, v: [f32; 2]) -> [f32; 2] {
+        let m = self.matrix;
+        [m[0][0] * v[0] + m[1][0] * v[1], m[0][1] * v[0] + m[1][1] * v[1]]
+    }
+
+    /// Transform a point in screen space.
+    fn apply(&self, point: [f32; 2], aspect_ratio: f32) -> [f32; 2] {
+        let [x, y] = self.apply_vector([point[0], point[1] / aspect_ratio]);
+        [x + self.translation[0], (y + self.translation[1]) * aspect_ratio]
+    }
+
+    /// Get the area that covers some bounds after they are transformed.
+    pub fn apply_bounds(&self, bounds: &Bounds, aspect_ratio: f32) -> Bounds {
+        let [x0, y0] = bounds.min;
+        let [x1, y1] = [x0 + bounds.size[0], y0 + bounds.size[1]];
+        let corners = [[x0, y0], [x1, y0], [x0, y1], [x1, y1]].map(|corner| self.apply(corner, aspect_ratio));
+        let min = [0, 1].map(|axis| corners.iter().map(|c| c[axis]).fold(f32::INFINITY, f32::min));
+        let max = [0, 1].map(|axis| corners.iter().map(|c| c[axis]).fold(f32::NEG_INFINITY, f32::max));
+        Bounds {
+            origin: self.apply(bounds.origin, aspect_ratio),
+            min,
+            size: [max[0] - min[0], max[1] - min[1]]
+        }
+    }
+}
+
+impl Bounds {
+    /// Get the area that covers both bounds. The origin is kept from `self`.
+    pub fn union(&self, other: &Bounds) -> Bounds {
+        let min = [0, 1].map(|axis| self.min[axis].min(other.min[axis]));
+        let max = [0, 1].map(|axis| (self.min[axis] + self.size[axis]).max(other.min[axis] + other.size[axis]));
+        Bounds {
+            origin: self.origin,
+            min,
+            size: [max[0] - min[0], max[1] - min[1]]
+        }
+    }
+}
+
+/// The combined transform and opacity of the groups that an object is in.
+#[derive(Clone, Copy)]
+pub struct Parent {
+    pub transform: Affine,
+    pub opacity: f32
+}
+
+impl Default for Parent {
+    fn default() -> Self {
+        Self {
+            transform: Affine::identity(),
+            opacity: 1.0
+        }
+    }
+}
+
+/// The parameters that every object has, which rotate and scale it around its anchor.
+pub struct TransformParameters {
+    rotation: Operation,
+    scale_x: Operation,
+    scale_y: Operation,
+    /// If not specified, the object is placed as it would be without an anchor.
+    anchor_x: Option<Operation>,
+    anchor_y: Option<Operation>,
+}
+
+impl TransformParameters {
+    pub fn new(mapping: &mut Mapping) -> Self {
+        Self {
+            rotation: mapping.get_or(RenderParam::Rotation, 0.0),
+            scale_x: mapping.get_or(RenderParam::ScaleX, 1.0),
+            scale_y: mapping.get_or(RenderParam::ScaleY, 1.0),
+            anchor_x: mapping.get_optional(RenderParam::AnchorX),
+            anchor_y: mapping.get_optional(RenderParam::AnchorY),
+        }
+    }
+
+    /// Get the transform of an object with the given bounds.
+    /// Coordinates are in screen space, with the y axis scaled to the same units as the x axis.
+    pub fn eval(&mut self, audio_packet: &AudioPacket, bounds: &Bounds, aspect_ratio: f32) -> Affine {
+        // Move the anchor onto the origin.
+        let mut offset = [0.0, 0.0];
+        for (axis, anchor) in [&mut self.anchor_x, &mut self.anchor_y].into_iter().enumerate() {
+            if let Some(anchor) = anchor {
+                let anchor = bounds.min[axis] + bounds.size[axis] * anchor.eval(audio_packet);
+                offset[axis] = bounds.origin[axis] - anchor;
+            }
+        }
+        let [x, y] = bounds.origin;
+        Affine::translate(offset[0] - x, (offset[1] - y) / aspect_ratio)
+            .then(Affine::scale(self.scale_x.eval(audio_packet), self.scale_y.eval(audio_packet)))
+            .then(Affine::rotate(self.rotation.eval(audio_packet)))
+            .then(Affine::translate(x, y / aspect_ratio))
+    }
+}
+
+/// Create the shader module of an object. The object's shader can use `apply_transform`.
+pub fn create_shader_module(device: &wgpu::Device, label: &str, source: &str) -> wgpu::ShaderModule {
+    device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some(label),
+        source: wgpu::ShaderSource::Wgsl(format!("{}\n{}", TRANSFORM_SHADER, source).into())
+    })
+}
+
+/// Create the layout of bind group 1, which every object's pipeline uses for its transform.
+pub fn create_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("transform"),
+        entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                ty: wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Storage { read_only: true }, has_dynamic_offset: false, min_binding_size: None },
+                count: None
+            }
+        ]
+    })
+}
+
+/// An object with a transform, which is applied by its shader.
+/// Each copy of a repeated object has its own transform.
+pub struct TransformedObject {
+    object: Box<dyn ObjectRenderable>,
+    /// One for each copy.
+    params: Vec<TransformParameters>,
+    /// The area that each copy covers, and its own transform, from the last update.
+    bounds: Vec<Bounds>,
+    transforms: Vec<Affine>,
+    parent: Parent,
+    uniform_data: Vec<f32>,
+    uniform_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup
+}
+
+impl TransformedObject {
+    pub fn new(object: Box<dyn ObjectRenderable>, params: Vec<TransformParameters>, renderer: &Renderer) -> Self {
+        let uniform_buffer = renderer.device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: (std::mem::size_of::<f32>() * 8 * params.len()) as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false
+        });
+        let bind_group = renderer.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: renderer.get_transform_layout(),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding()
+                }
+            ]
+        });
+        Self {
+            object,
+            params,
+            bounds: Vec::new(),
+            transforms: Vec::new(),
+            parent: Parent::default(),
+            uniform_data: Vec::new(),
+            uniform_buffer,
+            bind_group
+        }
+    }
+
+    fn write_uniforms(&mut self, renderer: &Renderer, aspect_ratio: f32) {
+        self.uniform_data.clear();
+        for transform in &self.transforms {
+            let transform = transform.then(self.parent.transform);
+            self.uniform_data.extend([
+                transform.matrix[0][0],
+                transform.matrix[0][1],
+                transform.matrix[1][0],
+                transform.matrix[1][1],
+                transform.translation[0],
+                transform.translation[1],
+                aspect_ratio,
+                self.parent.opacity
+            ]);
+        }
+        renderer.queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&self.uniform_data));
+    }
+}
+
+impl ObjectRenderable for TransformedObject {
+    fn update(&mut self, renderer: &Renderer, audio_packet: &AudioPacket, aspect_ratio: f32, bounds: &mut Vec<Bounds>) {
+        self.bounds.clear();
+        self.object.update(renderer, audio_packet, aspect_ratio, &mut self.bounds);
+        self.transforms.clear();
+        for (params, copy_bounds) in self.params.iter_mut().zip(&self.bounds) {
+            let transform = params.eval(audio_packet, copy_bounds, aspect_ratio);
+            bounds.push(transform.apply_bounds(copy_bounds, aspect_ratio));
+            self.transforms.push(transform);
+        }
+        self.write_uniforms(renderer, aspect_ratio);
+    }
+
+    fn set_parent(&mut self, renderer: &Renderer, parent: Parent, aspect_ratio: f32) {
+        self.parent = parent;
+        self.write_uniforms(renderer, aspect_ratio);
+    }
+
+    fn draw(&self, render_pass: &mut RenderPass<'_>) {
+        render_pass.render_pass.as_mut().unwrap().set_bind_group(1, &self.bind_group, &[]);
+        self.object.draw(render_pass);
+    }
+}
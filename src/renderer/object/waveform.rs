@@ -0,0 +1,215 @@
+use bytemuck::{Zeroable, Pod};
+
+use crate::{
+    audio::{AudioPacket, Channel, WAVEFORM_SIZE}, operation::Operation, renderer::{
+        Renderer, RenderPass, RenderParam, Mapping, BlendMode
+    }
+};
+use super::{ObjectRenderable, ObjectType, Bounds, transform};
+
+const VERTEX_COUNT: usize = WAVEFORM_SIZE * 2;
+
+#[derive(Zeroable, Pod, Clone, Copy)]
+#[repr(C)]
+struct Vertex {
+    pos: [f32; 2]
+}
+
+/// An oscilloscope line that shows the samples of the current frame.
+pub struct WaveformRenderable {
+    params: WaveformParameters,
+    pipeline: wgpu::RenderPipeline,
+    vertices: Vec<Vertex>,
+    vertex_buffer: wgpu::Buffer,
+    uniform_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup
+}
+
+/// The parameters of a waveform, taken from its mapping.
+pub struct WaveformParameters {
+    x: Operation,
+    y: Operation,
+    width: Operation,
+    height: Operation,
+    line_width: Operation,
+    r: Operation,
+    g: Operation,
+    b: Operation,
+    a: Operation,
+    channel: Channel,
+}
+
+impl WaveformParameters {
+    pub fn new(mapping: &mut Mapping) -> Self {
+        Self {
+            x: mapping.get(RenderParam::X),
+            y: mapping.get(RenderParam::Y),
+            width: mapping.get(RenderParam::Width),
+            height: mapping.get(RenderParam::Height),
+            line_width: mapping.get(RenderParam::LineWidth),
+            r: mapping.get(RenderParam::R),
+            g: mapping.get(RenderParam::G),
+            b: mapping.get(RenderParam::B),
+            a: mapping.get_or(RenderParam::A, 1.0),
+            channel: mapping.take_channel(),
+        }
+    }
+}
+
+impl WaveformRenderable {
+    pub fn create_pipeline(device: &wgpu::Device, transform_layout: &wgpu::BindGroupLayout, blend: BlendMode) -> wgpu::RenderPipeline {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                    ty: wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Uniform, has_dynamic_offset: false, min_binding_size: None },
+                    count: None
+                }
+            ]
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[&bind_group_layout, transform_layout],
+            push_constant_ranges: &[]
+        });
+        let shader_module = transform::create_shader_module(device, "waveform", include_str!("shaders/waveform.wgsl"));
+        let waveform_desc = wgpu::RenderPipelineDescriptor {
+            label: Some("waveform"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader_module,
+                entry_point: Some("vs_main"),
+                buffers: &[
+                    wgpu::VertexBufferLayout {
+                        array_stride: std::mem::size_of::<Vertex>() as u64,
+                        step_mode: wgpu::VertexStepMode::Vertex,
+                        attributes: &[
+                            wgpu::VertexAttribute {
+                                format: wgpu::VertexFormat::Float32x2,
+                                offset: 0,
+                                shader_location: 0
+                            }
+                        ]
+                    }
+                ],
+                compilation_options: Default::default()
+            },
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleStrip,
+                .. Default::default()
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader_module,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: wgpu::TextureFormat::Bgra8UnormSrgb,
+                    blend: Some(blend.blend_state()),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default()
+            }),
+            multiview: None,
+            cache: None
+        };
+        device.create_render_pipeline(&waveform_desc)
+    }
+
+    /// Create a new waveform to display on-screen.
+    pub fn new(params: WaveformParameters, blend: BlendMode, renderer: &Renderer) -> Self {
+        let vertex_buffer = renderer.device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: (std::mem::size_of::<Vertex>() * VERTEX_COUNT) as u64,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false
+        });
+        let uniform_buffer = renderer.device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: (std::mem::size_of::<f32>() as u64) * 4,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false
+        });
+        let pipeline = renderer.get_render_pipeline(ObjectType::Waveform, blend);
+        let bind_group_layout = pipeline.get_bind_group_layout(0);
+        let bind_group = renderer.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding()
+                }
+            ]
+        });
+        Self {
+            params,
+            pipeline,
+            vertices: vec![Vertex::zeroed(); VERTEX_COUNT],
+            vertex_buffer,
+            uniform_buffer,
+            bind_group
+        }
+    }
+}
+
+impl ObjectRenderable for WaveformRenderable {
+    fn update(&mut self, renderer: &Renderer, audio_packet: &AudioPacket, aspect_ratio: f32, bounds: &mut Vec<Bounds>) {
+        let x = self.params.x.eval(audio_packet);
+        let y = self.params.y.eval(audio_packet);
+        let width = self.params.width.eval(audio_packet);
+        let height = self.params.height.eval(audio_packet);
+        let half_line_width = self.params.line_width.eval(audio_packet) * 0.5;
+
+        // Points along the line, with the y axis scaled so that both axes have the same units.
+        let waveform = audio_packet.get_waveform(self.params.channel);
+        let last = (waveform.len() - 1) as f32;
+        let point = |i: usize| {
+            let px = x + width * (i as f32) / last;
+            let py = y + height * (waveform[i] + 1.0) * 0.5;
+            [px, py / aspect_ratio]
+        };
+
+        for i in 0..waveform.len() {
+            // Offset each point along the average normal of its neighbouring segments.
+            let prev = point(i.saturating_sub(1));
+            let next = point((i + 1).min(waveform.len() - 1));
+            let (dx, dy) = (next[0] - prev[0], next[1] - prev[1]);
+            let length = (dx * dx + dy * dy).sqrt().max(f32::EPSILON);
+            let normal = [-dy / length * half_line_width, dx / length * half_line_width];
+
+            let [px, py] = point(i);
+            self.vertices[i * 2] = Vertex { pos: [px + normal[0], (py + normal[1]) * aspect_ratio] };
+            self.vertices[i * 2 + 1] = Vertex { pos: [px - normal[0], (py - normal[1]) * aspect_ratio] };
+        }
+        renderer.queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&self.vertices));
+
+        let uniform_data = [
+            self.params.r.eval(audio_packet),
+            self.params.g.eval(audio_packet),
+            self.params.b.eval(audio_packet),
+            self.params.a.eval(audio_packet)
+        ];
+        renderer.queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&uniform_data));
+        bounds.push(Bounds {
+            origin: [x, y],
+            min: [x, y],
+            size: [width, height]
+        });
+    }
+
+    fn draw(&self, render_pass: &mut RenderPass<'_>) {
+        let render_pass = render_pass.render_pass.as_mut().unwrap();
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        let max = VERTEX_COUNT as u32;
+        render_pass.draw(0..max, 0..1);
+    }
+}
@@ -1,4 +1,4 @@
-use super::object::ObjectRenderable;
+use super::object::{ObjectRenderable, Bounds};
 use crate::{
     audio::AudioPacket,
     operation::Operation,
@@ -10,13 +10,14 @@ use crate::{
 /// and later be used to compose the final image.
 pub trait Scene {
     /// Set to use as the final display output.
-    fn set_display(&mut self, surface: &wgpu::SurfaceTexture);
+    fn set_display(&mut self, display: &wgpu::Texture);
 
     fn update(&mut self, renderer: &Renderer, audio_packet: &AudioPacket, size: Size);
 
     fn draw(&mut self, renderer: &Renderer);
 
-    // fn get_tex();
+    /// Get the texture that the scene renders to, when not used as the display.
+    fn get_tex(&self) -> Option<&wgpu::Texture>;
 }
 
 struct SceneTexture {
@@ -45,7 +46,7 @@ impl SceneTexture {
                 sample_count: 1,
                 dimension: wgpu::TextureDimension::D2,
                 format: wgpu::TextureFormat::Bgra8UnormSrgb,
-                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::RENDER_ATTACHMENT,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
                 view_formats: &[wgpu::TextureFormat::Bgra8UnormSrgb],
             };
             self.tex = Some(renderer.device.create_texture(&desc));
@@ -54,61 +55,81 @@ impl SceneTexture {
         }
     }
 
-    fn set_display(&mut self, surface: &wgpu::SurfaceTexture) {
-        self.view = Some(surface.texture.create_view(&Default::default()));
+    fn set_display(&mut self, display: &wgpu::Texture) {
+        self.view = Some(display.create_view(&Default::default()));
     }
 
     fn get_view(&mut self) -> wgpu::TextureView {
-        if self.view.is_none() && self.tex.is_some() {
-            self.view = Some(self.tex.as_ref().unwrap().create_view(&Default::default()));
+        if self.view.is_none() && let Some(tex) = &self.tex {
+            self.view = Some(tex.create_view(&Default::default()));
         }
         self.view.as_ref().unwrap().clone()
     }
 }
 
+/// The background colour of a render list.
+pub struct Background {
+    r: Operation,
+    g: Operation,
+    b: Operation
+}
+
+impl Background {
+    pub fn new(mut mapping: Mapping) -> Result<Self, Vec<CreationError>> {
+        let background = Self {
+            r: mapping.get(RenderParam::R),
+            g: mapping.get(RenderParam::G),
+            b: mapping.get(RenderParam::B)
+        };
+        mapping.check_parameters()?;
+        Ok(background)
+    }
+}
+
 pub struct RenderList {
     objects: Vec<Box<dyn ObjectRenderable>>,
+    /// The area that each object covers, from the last update. Kept so that it isn't allocated every frame.
+    bounds: Vec<Bounds>,
 
     color: [f64; 3],
-    r: Operation,
-    g: Operation,
-    b: Operation,
+    bg: Background,
 
     tex: SceneTexture
 }
 
 impl RenderList {
-    pub fn new(objects: Vec<Box<dyn ObjectRenderable>>, mut mapping: Mapping, _renderer: &Renderer) -> Result<Box<dyn Scene>, CreationError> {
-        Ok(Box::new(Self {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(objects: Vec<Box<dyn ObjectRenderable>>, bg: Background, _renderer: &Renderer) -> Box<dyn Scene> {
+        Box::new(Self {
             objects,
+            bounds: Vec::new(),
 
             color: [0.0, 0.0, 0.0],
-            r: mapping.get(RenderParam::R)?,
-            g: mapping.get(RenderParam::G)?,
-            b: mapping.get(RenderParam::B)?,
+            bg,
 
             tex: SceneTexture::new()
-        }))
+        })
     }
 }
 
 impl Scene for RenderList {
-    fn set_display(&mut self, surface: &wgpu::SurfaceTexture) {
-        self.tex.set_display(surface);
+    fn set_display(&mut self, display: &wgpu::Texture) {
+        self.tex.set_display(display);
     }
 
     fn update(&mut self, renderer: &Renderer, audio_packet: &AudioPacket, size: Size) {
         self.tex.update_size(renderer, size);
 
         self.color = [
-            self.r.eval(audio_packet).into(),
-            self.g.eval(audio_packet).into(),
-            self.b.eval(audio_packet).into()
+            self.bg.r.eval(audio_packet).into(),
+            self.bg.g.eval(audio_packet).into(),
+            self.bg.b.eval(audio_packet).into()
         ];
 
         let aspect_ratio = (size.width as f32) / (size.height as f32);
+        self.bounds.clear();
         for object in &mut self.objects {
-            object.update(renderer, audio_packet, aspect_ratio);
+            object.update(renderer, audio_packet, aspect_ratio, &mut self.bounds);
         }
     }
 
@@ -128,4 +149,8 @@ impl Scene for RenderList {
 
         render_pass.finish();
     }
+
+    fn get_tex(&self) -> Option<&wgpu::Texture> {
+        self.tex.tex.as_ref()
+    }
 }
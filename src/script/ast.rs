@@ -0,0 +1,138 @@
+use super::Span;
+
+/// A script as it was written, before any names have been checked.
+pub struct Script {
+    pub bindings: Vec<LetBinding>,
+    pub scene: Scene
+}
+
+/// `let name = value`
+pub struct LetBinding {
+    pub name: Name,
+    pub value: Expr
+}
+
+/// A name in the script, such as an object type or a parameter.
+pub struct Name {
+    pub text: String,
+    pub span: Span
+}
+
+pub enum Scene {
+    RenderList(RenderList)
+}
+
+/// `scene { bg = { ... } objects = [ ... ] }`
+pub struct RenderList {
+    /// The span of the `bg` keyword.
+    pub bg_span: Span,
+    pub bg: Vec<Param>,
+    pub objects: Vec<Object>
+}
+
+pub enum Object {
+    /// `object_type { params }`
+    Single(Name, Vec<Param>),
+    /// `group { params, objects = [ ... ] }`. The span is of the `group` keyword.
+    Group(Span, Vec<Param>, Vec<Object>),
+    /// `repeat count { object }`. The span is of the `repeat` keyword.
+    Repeat(Span, Expr, Box<Object>)
+}
+
+/// A string in quotes. The text doesn't include the quotes.
+pub struct StringLiteral {
+    pub text: String,
+    pub span: Span
+}
+
+pub enum Param {
+    /// `name = value`
+    Value(Name, Expr),
+    /// `name = "string"`
+    String(Name, StringLiteral),
+    /// `blend = mode`. The span is of the `blend` keyword.
+    Blend(Span, Name),
+    /// `channel = channel`. The span is of the `channel` keyword.
+    Channel(Span, Name),
+    /// `name = [(x, y), ...]`. The span is of the list.
+    Points(Name, Span, Vec<(Expr, Expr)>)
+}
+
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span
+}
+
+impl Expr {
+    /// Create a binary operation, which spans both sides.
+    pub fn binary(op: BinaryOp, l: Expr, r: Expr) -> Self {
+        let span = Span::new(l.span.start, r.span.end);
+        Self {
+            kind: ExprKind::Binary(op, Box::new(l), Box::new(r)),
+            span
+        }
+    }
+
+    /// Move the expression along in the source. Used for expressions that were parsed on their own.
+    pub fn offset(&mut self, offset: usize) {
+        self.span = self.span.offset(offset);
+        match &mut self.kind {
+            ExprKind::Number(_) | ExprKind::Name(_) | ExprKind::BarPhase(_) => {},
+            ExprKind::Channel(param, channel) => {
+                param.offset(offset);
+                channel.span = channel.span.offset(offset);
+            },
+            ExprKind::Neg(a) => a.offset(offset),
+            ExprKind::Band(a, b) | ExprKind::Binary(_, a, b) => {
+                a.offset(offset);
+                b.offset(offset);
+            },
+            ExprKind::If(cond, a, b) => {
+                cond.offset(offset);
+                a.offset(offset);
+                b.offset(offset);
+            },
+            ExprKind::Call(name, args) => {
+                name.span = name.span.offset(offset);
+                for arg in args {
+                    arg.offset(offset);
+                }
+            },
+        }
+    }
+}
+
+pub enum ExprKind {
+    Number(f32),
+    /// An audio parameter or a variable.
+    Name(String),
+    /// `band(lo, hi)`, where the frequencies must be constant.
+    Band(Box<Expr>, Box<Expr>),
+    /// `bar_phase` or `bar_phase(beats)`
+    BarPhase(Option<f32>),
+    /// `param.channel`
+    Channel(Box<Expr>, Name),
+    Neg(Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    /// `if cond then a else b`
+    If(Box<Expr>, Box<Expr>, Box<Expr>),
+    /// `function(args)`
+    Call(Name, Vec<Expr>)
+}
+
+#[derive(Clone, Copy)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+    And,
+    Or
+}
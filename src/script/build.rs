@@ -0,0 +1,13 @@
+use crate::renderer::{scene::RenderList, Renderer, Display};
+use super::validate::Program;
+
+impl Program {
+    /// Create the GPU resources for the script.
+    pub fn build(self, renderer: &Renderer) -> Display {
+        let objects = self.objects.into_iter()
+            .map(|object| object.create(renderer))
+            .collect::<Vec<_>>();
+        let scene = RenderList::new(objects, self.bg, renderer);
+        Display::new(self.bindings, scene, self.per_channel)
+    }
+}
@@ -0,0 +1,464 @@
+pub mod ast;
+mod validate;
+mod build;
+
+use lalrpop_util::lalrpop_mod;
+
+use crate::{
+    operation::MAX_PRECISION,
+    renderer::{object::{ObjectType, MAX_COPIES, text::MAX_GLYPHS}, Renderer, RenderParam, CreationError, Display}
+};
+
+pub use validate::{validate, Program};
+
+lalrpop_mod!(vis);
+
+/// A range of bytes in a script.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self {
+            start,
+            end
+        }
+    }
+
+    /// Move the span along by a number of bytes.
+    pub fn offset(self, offset: usize) -> Self {
+        Self::new(self.start + offset, self.end + offset)
+    }
+}
+
+/// An error in a script, and where it was found.
+#[derive(Debug)]
+pub struct ScriptError {
+    pub kind: ErrorKind,
+    pub span: Span
+}
+
+impl ScriptError {
+    pub fn new(kind: ErrorKind, span: Span) -> Self {
+        Self {
+            kind,
+            span
+        }
+    }
+
+    fn offset(self, offset: usize) -> Self {
+        Self::new(self.kind, self.span.offset(offset))
+    }
+
+    /// Convert an error from parsing `source`.
+    fn from_parse_error(err: lalrpop_util::ParseError<usize, vis::Token<'_>, &str>, source: &str) -> Self {
+        use lalrpop_util::ParseError::*;
+        match err {
+            User { error } => Self::new(ErrorKind::Syntax(error.to_string()), Span::default()),
+            InvalidToken { location } => {
+                // The invalid token is a single character, which may be more than one byte.
+                let len = source.get(location..).and_then(|rest| rest.chars().next()).map_or(1, char::len_utf8);
+                Self::new(ErrorKind::InvalidToken, Span::new(location, location + len))
+            },
+            ExtraToken { token: (l, t, r) } => Self::new(ErrorKind::ExtraToken(t.to_string()), Span::new(l, r)),
+            UnrecognizedEof { location, expected } => Self::new(ErrorKind::UnexpectedEof(expected), Span::new(location, location)),
+            UnrecognizedToken { token: (l, t, r), expected } => Self::new(ErrorKind::UnexpectedToken(t.to_string(), expected), Span::new(l, r)),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ErrorKind {
+    FileError(std::io::Error),
+    Syntax(String),
+    InvalidToken,
+    ExtraToken(String),
+    /// The tokens that were expected instead.
+    UnexpectedEof(Vec<String>),
+    /// The token that was found, and the tokens that were expected instead.
+    UnexpectedToken(String, Vec<String>),
+    UnrecognizedObject(String),
+    UnrecognizedAudioParam(String),
+    UnrecognizedRenderParam(String),
+    UnrecognizedChannel(String),
+    /// The name of an audio parameter that is the same for every channel.
+    UnexpectedChannel(String),
+    UnrecognizedBlendMode(String),
+    UnrecognizedFunction(String),
+    /// Function name, expected arguments, given arguments.
+    WrongArgumentCount(String, usize, usize),
+    InvalidBand(f32, f32),
+    NonConstantBand,
+    InvalidBarLength(f32),
+    DivisionByZero,
+    /// The name of a parameter that needs a string.
+    ExpectedString(String),
+    /// The name of a parameter that needs a number.
+    UnexpectedString(String),
+    UnmatchedBrace,
+    /// The format of a value inserted into a string.
+    InvalidFormat(String),
+    /// The number of characters in the string, not counting spaces.
+    TextTooLong(usize),
+    /// The reason that the image couldn't be loaded.
+    ImageError(String),
+    UndefinedVariable(String),
+    ReservedName(String),
+    DuplicateBinding(String),
+    /// The names of the variables in the cycle, in order.
+    CyclicBinding(Vec<String>),
+    BackgroundError(CreationError),
+    /// The type of the object, and its path in the object lists, such as `objects[1].objects[0]`.
+    ObjectError(ObjectType, String, CreationError),
+    /// The path of the group in the object lists.
+    GroupError(String, CreationError),
+    /// The name of the object that can't be repeated.
+    CannotRepeat(String),
+    InvalidRepeatCount,
+    /// The parameter, and the names that it can be set to.
+    InvalidStyle(RenderParam, &'static [&'static str]),
+    /// The name of a parameter that needs a list of points.
+    ExpectedPoints(String),
+    /// The name of a parameter that needs a number.
+    UnexpectedPoints(String),
+    TooFewPoints,
+}
+
+impl std::fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use ErrorKind::*;
+        match self {
+            FileError(e) =>                 write!(f, "could not read file: {}", e),
+            Syntax(s) =>                    write!(f, "{}", s),
+            InvalidToken =>                 write!(f, "invalid token"),
+            ExtraToken(t) =>                write!(f, "unexpected {}", t),
+            UnexpectedEof(_) =>             write!(f, "unexpected end of file"),
+            UnexpectedToken(t, _) =>        write!(f, "unexpected {}", t),
+            UnrecognizedObject(s) =>        write!(f, "unrecognized object {}", s),
+            UnrecognizedAudioParam(s) =>    write!(f, "unrecognized audio parameter {}", s),
+            UnrecognizedRenderParam(s) =>   write!(f, "unrecognized render parameter {}", s),
+            UnrecognizedChannel(s) =>       write!(f, "unrecognized channel {}", s),
+            UnexpectedChannel(s) =>         write!(f, "{} is the same for every channel, so it can't have a channel", s),
+            UnrecognizedBlendMode(s) =>     write!(f, "unrecognized blend mode {}", s),
+            UnrecognizedFunction(s) =>      write!(f, "unrecognized function {}", s),
+            WrongArgumentCount(func, expected, n) => write!(f, "function {} takes {} arguments, but {} were given", func, expected, n),
+            InvalidBand(lo, hi) =>          write!(f, "invalid frequency band {}..{} Hz", lo, hi),
+            NonConstantBand =>              write!(f, "frequency band must be constant: it can't use audio parameters or variables"),
+            InvalidBarLength(n) =>          write!(f, "invalid number of beats in bar: {}", n),
+            DivisionByZero =>               write!(f, "division by zero"),
+            ExpectedString(s) =>            write!(f, "parameter {} must be a string", s),
+            UnexpectedString(s) =>          write!(f, "parameter {} must be a number, not a string", s),
+            UnmatchedBrace =>               write!(f, "unmatched brace in string: use {{{{ or }}}} for a literal brace"),
+            ImageError(s) =>                write!(f, "could not load image: {}", s),
+            InvalidFormat(s) =>             write!(f, "invalid format :{}: expected a number of decimal places from 0 to {}, such as :.2", s, MAX_PRECISION),
+            TextTooLong(n) =>               write!(f, "text has {} characters, but at most {} can be drawn (not counting spaces)", n, MAX_GLYPHS),
+            UndefinedVariable(s) =>         write!(f, "unrecognized audio parameter or variable {}", s),
+            ReservedName(s) =>              write!(f, "cannot define variable {}: it is the name of an audio parameter", s),
+            DuplicateBinding(s) =>          write!(f, "variable {} is defined more than once", s),
+            CyclicBinding(names) =>         write!(f, "variables depend on each other: {}", names.join(" -> ")),
+            BackgroundError(e) =>           write!(f, "{} in scene background", e),
+            ObjectError(t, path, e) =>      write!(f, "{} in {} ({})", e, t, path),
+            GroupError(path, e) =>          write!(f, "{} in group ({})", e, path),
+            CannotRepeat(s) =>              write!(f, "{} can't be repeated", s),
+            InvalidRepeatCount =>           write!(f, "repeat count must be a constant whole number from 1 to {}", MAX_COPIES),
+            InvalidStyle(p, names) =>       write!(f, "{} must be one of: {}", p, names.join(", ")),
+            ExpectedPoints(s) =>            write!(f, "parameter {} must be a list of points, such as [(0, 0), (1, 1)]", s),
+            UnexpectedPoints(s) =>          write!(f, "parameter {} must be a number, not a list of points", s),
+            TooFewPoints =>                 write!(f, "a line needs at least 2 points"),
+        }
+    }
+}
+
+/// The errors in a script file, which display as compiler-style diagnostics.
+#[derive(Debug)]
+pub struct Report {
+    path: String,
+    source: String,
+    errors: Vec<ScriptError>
+}
+
+impl Report {
+    fn new(path: &str, source: String, errors: Vec<ScriptError>) -> Self {
+        Self {
+            path: path.to_string(),
+            source,
+            errors
+        }
+    }
+
+    /// Get the line and column of a location, both starting from 1.
+    fn line_col(&self, location: usize) -> (usize, usize) {
+        let before = self.source.get(..location.min(self.source.len())).unwrap_or("");
+        let line = before.matches('\n').count() + 1;
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        (line, before[line_start..].chars().count() + 1)
+    }
+}
+
+impl std::fmt::Display for Report {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for error in &self.errors {
+            writeln!(f, "error: {}", error.kind)?;
+            if let ErrorKind::FileError(_) = error.kind {
+                writeln!(f, "--> {}", self.path)?;
+                continue;
+            }
+
+            let (line, col) = self.line_col(error.span.start);
+            let line_text = self.source.lines().nth(line - 1).unwrap_or("");
+            let gutter = " ".repeat(line.to_string().len());
+            writeln!(f, "{}--> {}:{}:{}", gutter, self.path, line, col)?;
+            writeln!(f, "{} |", gutter)?;
+            writeln!(f, "{} | {}", line, line_text)?;
+
+            // Underline the span, up to the end of the line. Tabs are kept so that the caret lines up.
+            let indent = line_text.chars().take(col - 1)
+                .map(|c| if c == '\t' { '\t' } else { ' ' })
+                .collect::<String>();
+            let span_text = self.source.get(error.span.start.min(self.source.len())..error.span.end.min(self.source.len())).unwrap_or("");
+            let width = span_text.lines().next().unwrap_or("").chars().count().max(1);
+            writeln!(f, "{} | {}{}", gutter, indent, "^".repeat(width))?;
+
+            if let ErrorKind::UnexpectedEof(expected) | ErrorKind::UnexpectedToken(_, expected) = &error.kind && !expected.is_empty() {
+                writeln!(f, "{} = expected one of: {}", gutter, expected.join(", "))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parse a script into a syntax tree. This only checks the syntax of the script.
+pub fn parse(source: &str) -> Result<ast::Script, ScriptError> {
+    vis::ScriptParser::new().parse(source)
+        .map_err(|e| ScriptError::from_parse_error(e, source))
+}
+
+/// Parse an expression that starts at `offset` in a script, such as a value inserted into a string.
+pub fn parse_expression(source: &str, offset: usize) -> Result<ast::Expr, ScriptError> {
+    let mut expr = vis::OperationParser::new().parse(source)
+        .map_err(|e| ScriptError::from_parse_error(e, source).offset(offset))?;
+    expr.offset(offset);
+    Ok(expr)
+}
+
+/// Read a script file, and parse and validate it. This doesn't need a GPU.
+pub fn load_file(file_path: &str) -> Result<Program, Report> {
+    let source = std::fs::read_to_string(file_path)
+        .map_err(|e| Report::new(file_path, String::new(), vec![ScriptError::new(ErrorKind::FileError(e), Span::default())]))?;
+    // Files in the script are relative to the script itself.
+    let base_dir = std::path::Path::new(file_path).parent().unwrap_or(std::path::Path::new(""));
+    parse(&source).map_err(|e| vec![e])
+        .and_then(|script| validate(&script, base_dir))
+        .map_err(|errors| Report::new(file_path, source, errors))
+}
+
+/// Read a script file, and build a display from it.
+pub fn parse_file(file_path: &str, renderer: &Renderer) -> Result<Display, Report> {
+    Ok(load_file(file_path)?.build(renderer))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ast::{Expr, ExprKind, BinaryOp, Object, Param};
+
+    /// Write an expression with every operation in brackets, to show how it was parsed.
+    fn show(expr: &Expr) -> String {
+        match &expr.kind {
+            ExprKind::Number(n) => n.to_string(),
+            ExprKind::Name(name) => name.clone(),
+            ExprKind::Band(lo, hi) => format!("band({}, {})", show(lo), show(hi)),
+            ExprKind::BarPhase(None) => "bar_phase".to_string(),
+            ExprKind::BarPhase(Some(beats)) => format!("bar_phase({beats})"),
+            ExprKind::Channel(param, channel) => format!("{}.{}", show(param), channel.text),
+            ExprKind::Neg(a) => format!("-{}", show(a)),
+            ExprKind::Binary(op, a, b) => {
+                let op = match op {
+                    BinaryOp::Add => "+",
+                    BinaryOp::Sub => "-",
+                    BinaryOp::Mul => "*",
+                    BinaryOp::Div => "/",
+                    BinaryOp::Mod => "%",
+                    BinaryOp::Lt => "<",
+                    BinaryOp::Le => "<=",
+                    BinaryOp::Gt => ">",
+                    BinaryOp::Ge => ">=",
+                    BinaryOp::Eq => "==",
+                    BinaryOp::Ne => "!=",
+                    BinaryOp::And => "and",
+                    BinaryOp::Or => "or",
+                };
+                format!("({} {} {})", show(a), op, show(b))
+            },
+            ExprKind::If(cond, a, b) => format!("(if {} then {} else {})", show(cond), show(a), show(b)),
+            ExprKind::Call(name, args) => format!("{}({})", name.text, args.iter().map(show).collect::<Vec<_>>().join(", ")),
+        }
+    }
+
+    fn parse_shown(source: &str) -> String {
+        show(&parse_expression(source, 0).unwrap())
+    }
+
+    /// Parse a script with the given objects and an empty background.
+    fn parse_objects(objects: &str) -> Vec<Object> {
+        let source = format!("display = scene {{ bg = {{ r = 0, g = 0, b = 0 }} objects = [ {objects} ] }}");
+        match parse(&source) {
+            Ok(script) => match script.scene {
+                ast::Scene::RenderList(render_list) => render_list.objects
+            },
+            Err(e) => panic!("{} at {:?}", e.kind, e.span),
+        }
+    }
+
+    #[test]
+    fn operator_precedence() {
+        assert_eq!(parse_shown("1 + 2 * 3 - 4"), "((1 + (2 * 3)) - 4)");
+        assert_eq!(parse_shown("a / b % c * d"), "(((a / b) % c) * d)");
+        assert_eq!(parse_shown("(1 + 2) * 3"), "((1 + 2) * 3)");
+        assert_eq!(parse_shown("a + 1 < b * 2"), "((a + 1) < (b * 2))");
+        assert_eq!(parse_shown("a < 1 or b >= 2 and c != 3"), "((a < 1) or ((b >= 2) and (c != 3)))");
+        assert_eq!(parse_shown("a == b and c <= d"), "((a == b) and (c <= d))");
+        assert_eq!(parse_shown("min(a + 1, max(b, 2) * 3)"), "min((a + 1), (max(b, 2) * 3))");
+    }
+
+    #[test]
+    fn comparisons_dont_chain() {
+        assert!(parse_expression("a < b < c", 0).is_err());
+    }
+
+    #[test]
+    fn if_then_else() {
+        assert_eq!(parse_shown("if a > 1 then 2 else 3 + 4"), "(if (a > 1) then 2 else (3 + 4))");
+        assert_eq!(parse_shown("if a then if b then 1 else 2 else 3"), "(if a then (if b then 1 else 2) else 3)");
+        assert_eq!(parse_shown("1 + (if a then 2 else 3)"), "(1 + (if a then 2 else 3))");
+        let expr = parse_expression("if a then 1 else 2", 4).unwrap();
+        assert_eq!(expr.span, Span::new(4, 22));
+    }
+
+    #[test]
+    fn unary_minus_folds_into_numbers() {
+        let expr = parse_expression("-2.5", 0).unwrap();
+        assert!(matches!(expr.kind, ExprKind::Number(n) if n == -2.5));
+        assert_eq!(expr.span, Span::new(0, 4));
+        assert!(matches!(parse_expression("--2", 0).unwrap().kind, ExprKind::Number(2.0)));
+        assert_eq!(parse_shown("-x"), "-x");
+        assert_eq!(parse_shown("-(1 + 2)"), "-(1 + 2)");
+        assert_eq!(parse_shown("-2 * 3"), "(-2 * 3)");
+        assert_eq!(parse_shown("1 - -2"), "(1 - -2)");
+        assert_eq!(parse_shown("-x.left"), "-x.left");
+    }
+
+    #[test]
+    fn audio_params() {
+        assert_eq!(parse_shown("level.left + bass"), "(level.left + bass)");
+        assert_eq!(parse_shown("band(100, 200.5).right"), "band(100, 200.5).right");
+        assert_eq!(parse_shown("band(i * 100, (i + 1) * 100)"), "band((i * 100), ((i + 1) * 100))");
+        assert_eq!(parse_shown("bar_phase + bar_phase(3)"), "(bar_phase + bar_phase(3))");
+    }
+
+    #[test]
+    fn string_literals() {
+        let objects = parse_objects(r#"text { text = "time: {time:.1}", x = 0 }"#);
+        let Object::Single(name, params) = &objects[0] else { panic!("expected an object") };
+        assert_eq!(name.text, "text");
+        let Param::String(param, literal) = &params[0] else { panic!("expected a string") };
+        assert_eq!(param.text, "text");
+        assert_eq!(literal.text, "time: {time:.1}");
+        assert_eq!(literal.span.end - literal.span.start, literal.text.len() + 2);
+        assert!(matches!(params[1], Param::Value(..)));
+    }
+
+    #[test]
+    fn groups_repeats_and_points() {
+        let objects = parse_objects("\
+            group { x = 1, y = 2, objects = [ quad { x = 0 } ] },
+            group { objects = [ circle { x = 0 }, repeat 2 { quad { x = i } } ] },
+            repeat 4 * 2 { polyline { points = [(0, 0), (i, 1), (-1, 2)] } }");
+        assert_eq!(objects.len(), 3);
+
+        let Object::Group(_, params, children) = &objects[0] else { panic!("expected a group") };
+        assert_eq!((params.len(), children.len()), (2, 1));
+        let Object::Group(_, params, children) = &objects[1] else { panic!("expected a group") };
+        assert_eq!((params.len(), children.len()), (0, 2));
+        assert!(matches!(&children[1], Object::Repeat(..)));
+
+        let Object::Repeat(_, count, object) = &objects[2] else { panic!("expected a repeat") };
+        assert_eq!(show(count), "(4 * 2)");
+        let Object::Single(_, params) = object.as_ref() else { panic!("expected an object") };
+        let Param::Points(name, _, points) = &params[0] else { panic!("expected points") };
+        assert_eq!(name.text, "points");
+        let points = points.iter().map(|(x, y)| format!("{}, {}", show(x), show(y))).collect::<Vec<_>>();
+        assert_eq!(points, ["0, 0", "i, 1", "-1, 2"]);
+    }
+
+    #[test]
+    fn unexpected_tokens_list_what_was_expected() {
+        let source = "display = scene { bg = { r = 0 } objects = [ quad { x = } ] }";
+        let error = parse(source).err().unwrap();
+        let ErrorKind::UnexpectedToken(token, expected) = &error.kind else { panic!("{}", error.kind) };
+        assert_eq!(token, "}");
+        assert_eq!(&source[error.span.start..error.span.end], "}");
+        assert!(expected.iter().any(|t| t == "\"(\""), "{expected:?}");
+        assert!(expected.iter().any(|t| t == "\"if\""), "{expected:?}");
+        // Terminals that match patterns are named in the grammar, rather than shown as regexes.
+        for name in ["name", "number", "string"] {
+            assert!(expected.iter().any(|t| t == name), "{expected:?}");
+        }
+        assert!(expected.iter().all(|t| t.starts_with('"') || t.chars().all(|c| c.is_ascii_lowercase())), "{expected:?}");
+
+        let report = Report::new("test.vis", source.to_string(), vec![error]).to_string();
+        assert!(report.contains("expected one of: "), "{report}");
+        assert!(report.contains("name"), "{report}");
+        assert!(report.contains("number"), "{report}");
+    }
+
+    #[test]
+    fn names_and_numbers_are_expected_by_name() {
+        let error = parse("let 5 = 3").err().unwrap();
+        let ErrorKind::UnexpectedToken(token, expected) = &error.kind else { panic!("{}", error.kind) };
+        assert_eq!(token, "5");
+        assert_eq!(expected, &["name"]);
+
+        let error = parse("let a = band(").err().unwrap();
+        let ErrorKind::UnexpectedEof(expected) = &error.kind else { panic!("{}", error.kind) };
+        assert!(expected.iter().any(|t| t == "number"), "{expected:?}");
+    }
+
+    #[test]
+    fn unexpected_end_of_file() {
+        let source = "display = scene { bg = { r = 0 }";
+        let error = parse(source).err().unwrap();
+        let ErrorKind::UnexpectedEof(expected) = &error.kind else { panic!("{}", error.kind) };
+        assert_eq!(expected, &["\"objects\""]);
+        assert_eq!(error.span, Span::new(source.len(), source.len()));
+    }
+
+    #[test]
+    fn invalid_tokens_span_whole_characters() {
+        for (source, token) in [
+            ("display = scene { bg = { r = “1.0”, g = 0, b = 0 } objects = [] }", "“"),
+            ("let levél = 1\ndisplay = scene { bg = { r = 0, g = 0, b = 0 } objects = [] }", "é"),
+        ] {
+            let error = parse(source).err().unwrap();
+            assert!(matches!(error.kind, ErrorKind::InvalidToken));
+            assert_eq!(&source[error.span.start..error.span.end], token);
+
+            let report = Report::new("test.vis", source.to_string(), vec![error]).to_string();
+            let col = source.split(token).next().unwrap().chars().count() + 1;
+            assert!(report.contains(&format!("test.vis:1:{col}")), "{report}");
+        }
+    }
+
+    #[test]
+    fn reports_clamp_spans_inside_the_source() {
+        let source = "é\n";
+        let errors = vec![
+            ScriptError::new(ErrorKind::InvalidToken, Span::new(1, 2)),
+            ScriptError::new(ErrorKind::InvalidToken, Span::new(10, 12)),
+        ];
+        Report::new("test.vis", source.to_string(), errors).to_string();
+    }
+}
@@ -0,0 +1,915 @@
+use std::{collections::HashMap, path::{Path, PathBuf}, rc::Rc, str::FromStr};
+
+use crate::{
+    audio::{AudioParam, Channel, MetadataField},
+    operation::{Operation, Function, Filter, FilterKind, Binding, Variable, TextTemplate, TextPart, MAX_PRECISION},
+    renderer::{object::{ObjectDesc, ObjectType, MAX_COPIES, image::ImageData, shape::{Join, Cap}, text::MAX_GLYPHS}, scene::Background, Mapping, RenderParam}
+};
+use strum::VariantNames;
+use super::{
+    ast::{self, Expr, ExprKind, BinaryOp, Name},
+    ErrorKind, ScriptError, Span
+};
+
+/// A script that has been checked, with every name resolved.
+/// It can be built into a display without any further errors in the script itself.
+pub struct Program {
+    /// Sorted so that each binding comes after the variables that it reads.
+    pub(super) bindings: Vec<Binding>,
+    pub(super) bg: Background,
+    pub(super) objects: Vec<ObjectDesc>,
+    /// Whether anything reads a single channel of the audio.
+    pub(super) per_channel: bool
+}
+
+/// Check a script and resolve all of its names. Returns every error that is found.
+/// Files that the script uses are loaded relative to `base_dir`.
+pub fn validate(script: &ast::Script, base_dir: &Path) -> Result<Program, Vec<ScriptError>> {
+    let mut validator = Validator::new(base_dir);
+
+    let bindings = script.bindings.iter()
+        .filter_map(|binding| validator.binding(binding))
+        .collect::<Vec<_>>();
+    let (bg, objects) = match &script.scene {
+        ast::Scene::RenderList(render_list) => {
+            let bg = validator.background(render_list);
+            let objects = validator.objects("objects", &render_list.objects);
+            (bg, objects)
+        }
+    };
+    validator.check_variables(script);
+    let bindings = sort_bindings(bindings)
+        .map_err(|e| validator.errors.push(e))
+        .ok();
+
+    if !validator.errors.is_empty() {
+        validator.errors.sort_by_key(|e| e.span.start);
+        return Err(validator.errors);
+    }
+    Ok(Program {
+        bindings: bindings.expect("bindings are sorted if there are no errors"),
+        bg: bg.expect("background is valid if there are no errors"),
+        objects,
+        per_channel: validator.per_channel
+    })
+}
+
+struct Validator<'a> {
+    base_dir: &'a Path,
+    /// Every variable that is used in an expression, and where it was first used.
+    variables: Vec<(Rc<Variable>, Span)>,
+    /// Every variable that is defined or used, by name.
+    names: HashMap<String, Rc<Variable>>,
+    /// The index and number of copies, while checking a copy of a repeated object.
+    copy: Option<(usize, usize)>,
+    /// Every image that has been loaded, so that repeated objects share their image.
+    images: HashMap<PathBuf, Rc<ImageData>>,
+    /// Whether a single channel has been used so far.
+    per_channel: bool,
+    errors: Vec<ScriptError>
+}
+
+impl<'a> Validator<'a> {
+    fn new(base_dir: &'a Path) -> Self {
+        Self {
+            base_dir,
+            variables: Vec::new(),
+            names: HashMap::new(),
+            copy: None,
+            images: HashMap::new(),
+            per_channel: false,
+            errors: Vec::new()
+        }
+    }
+
+    fn error(&mut self, kind: ErrorKind, span: Span) {
+        self.errors.push(ScriptError::new(kind, span));
+    }
+
+    /// Resolve a name, or report it as unrecognized.
+    fn resolve<T: FromStr>(&mut self, name: &Name, error: fn(String) -> ErrorKind) -> Option<T> {
+        let value = T::from_str(&name.text).ok();
+        if value.is_none() {
+            self.error(error(name.text.clone()), name.span);
+        }
+        value
+    }
+
+    /// Resolve the name of a channel, and note whether it is a single channel.
+    fn channel(&mut self, name: &Name) -> Option<Channel> {
+        let channel = self.resolve(name, ErrorKind::UnrecognizedChannel)?;
+        self.per_channel |= !matches!(channel, Channel::Both);
+        Some(channel)
+    }
+
+    /// Get the variable with a name, creating it if it hasn't been seen before.
+    fn variable(&mut self, name: &str) -> Rc<Variable> {
+        self.names.entry(name.to_string())
+            .or_insert_with(|| Rc::new(Variable::new(name.to_string())))
+            .clone()
+    }
+
+    fn binding(&mut self, binding: &ast::LetBinding) -> Option<(Binding, Span)> {
+        let name = &binding.name;
+        if AudioParam::from_str(&name.text).is_ok() {
+            self.error(ErrorKind::ReservedName(name.text.clone()), name.span);
+        }
+        let value = self.expression(&binding.value)?;
+        Some((Binding::new(self.variable(&name.text), value), name.span))
+    }
+
+    /// Check that every variable is defined exactly once.
+    fn check_variables(&mut self, script: &ast::Script) {
+        let mut defined = HashMap::new();
+        for binding in &script.bindings {
+            if defined.insert(binding.name.text.as_str(), binding.name.span).is_some() {
+                self.error(ErrorKind::DuplicateBinding(binding.name.text.clone()), binding.name.span);
+            }
+        }
+        let undefined = self.variables.iter()
+            .filter(|(variable, _)| !defined.contains_key(variable.name()))
+            .map(|(variable, span)| ScriptError::new(ErrorKind::UndefinedVariable(variable.name().to_string()), *span))
+            .collect::<Vec<_>>();
+        self.errors.extend(undefined);
+    }
+
+    fn background(&mut self, render_list: &ast::RenderList) -> Option<Background> {
+        let (mapping, valid) = self.mapping(&render_list.bg);
+        Background::new(mapping)
+            .map_err(|errors| for e in errors.into_iter().filter(|e| valid || !e.depends_on_values()) {
+                self.error(ErrorKind::BackgroundError(e), e.span().unwrap_or(render_list.bg_span));
+            })
+            .ok()
+            .filter(|_| valid)
+    }
+
+    /// Check a list of objects. `path` is where the list is, such as `objects[1].objects`.
+    fn objects(&mut self, path: &str, objects: &[ast::Object]) -> Vec<ObjectDesc> {
+        objects.iter().enumerate()
+            .filter_map(|(index, object)| self.object(format!("{}[{}]", path, index), object))
+            .collect()
+    }
+
+    fn object(&mut self, path: String, object: &ast::Object) -> Option<ObjectDesc> {
+        match object {
+            ast::Object::Single(name, params) => self.single_object(path, name, params, None),
+            ast::Object::Group(span, params, objects) => {
+                let (mapping, valid) = self.mapping(params);
+                let children = self.objects(&format!("{}.objects", path), objects);
+                ObjectDesc::group(mapping, children)
+                    .map_err(|errors| for e in errors.into_iter().filter(|e| valid || !e.depends_on_values()) {
+                        self.error(ErrorKind::GroupError(path.clone(), e), e.span().unwrap_or(*span));
+                    })
+                    .ok()
+                    .filter(|_| valid)
+            },
+            ast::Object::Repeat(_, count, object) => {
+                let count = self.repeat_count(count);
+                match object.as_ref() {
+                    ast::Object::Single(name, params) => self.single_object(path, name, params, Some(count?)),
+                    ast::Object::Group(span, ..) => {
+                        self.error(ErrorKind::CannotRepeat("group".to_string()), *span);
+                        None
+                    },
+                    ast::Object::Repeat(span, ..) => {
+                        self.error(ErrorKind::CannotRepeat("repeat".to_string()), *span);
+                        None
+                    }
+                }
+            }
+        }
+    }
+
+    /// Check an object that isn't a group, with the number of copies if it is repeated.
+    fn single_object(&mut self, path: String, name: &Name, params: &[ast::Param], count: Option<usize>) -> Option<ObjectDesc> {
+        let object_type: Option<ObjectType> = self.resolve(name, ErrorKind::UnrecognizedObject);
+        if count.is_some() && object_type.is_some_and(|object_type| !object_type.can_repeat()) {
+            self.error(ErrorKind::CannotRepeat(name.text.clone()), name.span);
+            return None;
+        }
+        // Each copy has its own operations, so that functions such as `smooth` keep a separate state for each copy.
+        let mut mappings = Vec::new();
+        let mut valid = true;
+        for index in 0..count.unwrap_or(1) {
+            self.copy = count.map(|count| (index, count));
+            let (mapping, copy_valid) = self.mapping(params);
+            self.copy = None;
+            mappings.push(mapping);
+            // Stop at the first copy with errors, so that each error is only reported once.
+            if !copy_valid {
+                valid = false;
+                break;
+            }
+        }
+        let object_type = object_type?;
+        ObjectDesc::new(object_type, mappings)
+            .map_err(|errors| for e in errors.into_iter().filter(|e| valid || !e.depends_on_values()) {
+                self.error(ErrorKind::ObjectError(object_type, path.clone(), e), e.span().unwrap_or(name.span));
+            })
+            .ok()
+            .filter(|_| valid)
+    }
+
+    /// Get the number of copies of a repeated object, which must be the same on every frame.
+    fn repeat_count(&mut self, count: &Expr) -> Option<usize> {
+        let value = self.expression(count)?.constant_value();
+        match value {
+            Some(n) if n.fract() == 0.0 && (1.0..=(MAX_COPIES as f32)).contains(&n) => Some(n as usize),
+            _ => {
+                self.error(ErrorKind::InvalidRepeatCount, count.span);
+                None
+            }
+        }
+    }
+
+    /// Check the parameters of an object. Returns the mapping, and whether every parameter was valid.
+    /// Parameters with errors are given placeholder values, so that they aren't also reported as missing.
+    fn mapping(&mut self, params: &[ast::Param]) -> (Mapping, bool) {
+        let error_count = self.errors.len();
+        let mut mapping = Mapping::default();
+        for param in params {
+            mapping = match param {
+                ast::Param::Value(name, value) => match self.resolve(name, ErrorKind::UnrecognizedRenderParam) {
+                    Some(param @ (RenderParam::Text | RenderParam::File)) => {
+                        self.error(ErrorKind::ExpectedString(name.text.clone()), value.span);
+                        mapping.add_placeholder(param, name.span)
+                    },
+                    Some(RenderParam::Points) => {
+                        self.error(ErrorKind::ExpectedPoints(name.text.clone()), value.span);
+                        mapping.add_placeholder(RenderParam::Points, name.span)
+                    },
+                    Some(RenderParam::Join) => match self.style(RenderParam::Join, value) {
+                        Some(join) => mapping.set_join((join, name.span)),
+                        None => mapping.add_placeholder(RenderParam::Join, name.span)
+                    },
+                    Some(RenderParam::Cap) => match self.style(RenderParam::Cap, value) {
+                        Some(cap) => mapping.set_cap((cap, name.span)),
+                        None => mapping.add_placeholder(RenderParam::Cap, name.span)
+                    },
+                    Some(param) => match self.expression(value) {
+                        Some(operation) => mapping.add((param, operation, name.span)),
+                        None => mapping.add_placeholder(param, name.span)
+                    },
+                    None => {
+                        // Check the value anyway, to report any errors in it.
+                        self.expression(value);
+                        mapping
+                    }
+                },
+                ast::Param::String(name, literal) => match self.resolve(name, ErrorKind::UnrecognizedRenderParam) {
+                    Some(RenderParam::Text) => match self.text(literal) {
+                        Some(text) => mapping.set_text((text, name.span)),
+                        None => mapping.add_placeholder(RenderParam::Text, name.span)
+                    },
+                    Some(RenderParam::File) => match self.image(literal) {
+                        Some(image) => mapping.set_image((image, name.span)),
+                        None => mapping.add_placeholder(RenderParam::File, name.span)
+                    },
+                    Some(RenderParam::Points) => {
+                        self.error(ErrorKind::ExpectedPoints(name.text.clone()), literal.span);
+                        mapping.add_placeholder(RenderParam::Points, name.span)
+                    },
+                    Some(RenderParam::Join) => {
+                        self.error(ErrorKind::InvalidStyle(RenderParam::Join, Join::VARIANTS), literal.span);
+                        mapping.add_placeholder(RenderParam::Join, name.span)
+                    },
+                    Some(RenderParam::Cap) => {
+                        self.error(ErrorKind::InvalidStyle(RenderParam::Cap, Cap::VARIANTS), literal.span);
+                        mapping.add_placeholder(RenderParam::Cap, name.span)
+                    },
+                    Some(param) => {
+                        self.error(ErrorKind::UnexpectedString(name.text.clone()), literal.span);
+                        mapping.add_placeholder(param, name.span)
+                    },
+                    None => mapping
+                },
+                ast::Param::Blend(span, name) => match self.resolve(name, ErrorKind::UnrecognizedBlendMode) {
+                    Some(blend) => mapping.set_blend((blend, *span)),
+                    None => mapping.add_placeholder(RenderParam::Blend, *span)
+                },
+                ast::Param::Channel(span, name) => match self.channel(name) {
+                    Some(channel) => mapping.set_channel((channel, *span)),
+                    None => mapping.add_placeholder(RenderParam::Channel, *span)
+                },
+                ast::Param::Points(name, span, points) => {
+                    let param = self.resolve(name, ErrorKind::UnrecognizedRenderParam);
+                    // Check every point, even if one of them has an error.
+                    let points = points.iter()
+                        .map(|(x, y)| (self.expression(x), self.expression(y)))
+                        .collect::<Vec<_>>();
+                    let points = points.into_iter()
+                        .map(|(x, y)| Some([x?, y?]))
+                        .collect::<Option<Vec<_>>>();
+                    match (param, points) {
+                        (Some(RenderParam::Points), Some(points)) if points.len() < 2 => {
+                            self.error(ErrorKind::TooFewPoints, *span);
+                            mapping.add_placeholder(RenderParam::Points, name.span)
+                        },
+                        (Some(RenderParam::Points), Some(points)) => mapping.set_points((points, name.span)),
+                        (Some(RenderParam::Points), None) => mapping.add_placeholder(RenderParam::Points, name.span),
+                        (Some(param), _) => {
+                            self.error(ErrorKind::UnexpectedPoints(name.text.clone()), *span);
+                            mapping.add_placeholder(param, name.span)
+                        },
+                        (None, _) => mapping
+                    }
+                },
+            }
+        }
+        let valid = self.errors.len() == error_count;
+        (mapping, valid)
+    }
+
+    /// Get a style that is set with a name rather than an expression, such as `join = round`.
+    fn style<T: FromStr + VariantNames>(&mut self, param: RenderParam, value: &Expr) -> Option<T> {
+        let style = match &value.kind {
+            ExprKind::Name(name) => T::from_str(name).ok(),
+            _ => None
+        };
+        if style.is_none() {
+            self.error(ErrorKind::InvalidStyle(param, T::VARIANTS), value.span);
+        }
+        style
+    }
+
+    /// Load an image file, relative to the script.
+    fn image(&mut self, literal: &ast::StringLiteral) -> Option<Rc<ImageData>> {
+        let path = self.base_dir.join(&literal.text);
+        if let Some(image) = self.images.get(&path) {
+            return Some(image.clone());
+        }
+        let image = ImageData::load(&path)
+            .map(Rc::new)
+            .map_err(|e| self.error(ErrorKind::ImageError(e), literal.span))
+            .ok()?;
+        self.images.insert(path, image.clone());
+        Some(image)
+    }
+
+    /// Split a string into literal text and values in braces, such as `"{bpm:.0} BPM"`.
+    /// All of the values are checked, even if one of them has an error.
+    fn text(&mut self, literal: &ast::StringLiteral) -> Option<TextTemplate> {
+        // The text starts after the opening quote.
+        let start = literal.span.start + 1;
+        let text = literal.text.as_str();
+        let mut parts = Vec::new();
+        let mut current = String::new();
+        let mut valid = true;
+        let mut chars = text.char_indices().peekable();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '{' if chars.next_if(|(_, c)| *c == '{').is_some() => current.push('{'),
+                '}' if chars.next_if(|(_, c)| *c == '}').is_some() => current.push('}'),
+                '{' => {
+                    let Some(length) = text[i + 1..].find('}') else {
+                        self.error(ErrorKind::UnmatchedBrace, Span::new(start + i, start + i + 1));
+                        return None;
+                    };
+                    let end = i + 1 + length;
+                    while chars.next_if(|(j, _)| *j <= end).is_some() {}
+                    if !current.is_empty() {
+                        parts.push(TextPart::Literal(std::mem::take(&mut current)));
+                    }
+                    match self.text_value(&text[i + 1..end], start + i + 1) {
+                        Some(part) => parts.push(part),
+                        None => valid = false
+                    }
+                },
+                '}' => {
+                    self.error(ErrorKind::UnmatchedBrace, Span::new(start + i, start + i + 1));
+                    valid = false;
+                },
+                c => current.push(c)
+            }
+        }
+        if !current.is_empty() {
+            parts.push(TextPart::Literal(current));
+        }
+        // Spaces aren't drawn, so they don't count towards the limit.
+        let glyph_count = parts.iter()
+            .map(|part| match part {
+                TextPart::Literal(s) => s.chars().filter(|c| !c.is_whitespace()).count(),
+                _ => 0
+            })
+            .sum::<usize>();
+        if glyph_count > MAX_GLYPHS {
+            self.error(ErrorKind::TextTooLong(glyph_count), literal.span);
+            valid = false;
+        }
+        valid.then(|| TextTemplate::new(parts))
+    }
+
+    /// Convert a value inserted into a string, which starts at `offset` in the script.
+    /// This is either a metadata field, or an expression with an optional number of decimal places.
+    fn text_value(&mut self, source: &str, offset: usize) -> Option<TextPart> {
+        let (expr, format) = match source.rsplit_once(':') {
+            Some((expr, format)) => (expr, Some(format)),
+            None => (source, None)
+        };
+        if format.is_none() && let Ok(field) = MetadataField::from_str(source.trim()) {
+            return Some(TextPart::Metadata(field));
+        }
+        // The number of decimal places, if it is valid.
+        let precision = match format {
+            Some(format) => match format.strip_prefix('.').and_then(|n| n.parse::<usize>().ok()) {
+                Some(precision) if precision <= MAX_PRECISION => Some(Some(precision)),
+                _ => {
+                    let format_start = offset + expr.len() + 1;
+                    self.error(ErrorKind::InvalidFormat(format.to_string()), Span::new(format_start, format_start + format.len()));
+                    None
+                }
+            },
+            None => Some(None)
+        };
+        let operation = match super::parse_expression(expr, offset) {
+            Ok(expr) => self.expression(&expr),
+            Err(e) => {
+                self.errors.push(e);
+                None
+            }
+        };
+        Some(TextPart::Value(operation?, precision?))
+    }
+
+    /// Convert an expression into an operation.
+    /// All of the sub-expressions are checked, even if one of them has an error.
+    fn expression(&mut self, expr: &Expr) -> Option<Operation> {
+        Some(match &expr.kind {
+            ExprKind::Number(n) => Operation::Const(*n),
+            ExprKind::Name(name) => match (self.copy_value(name), AudioParam::from_str(name)) {
+                (Some(value), _) => Operation::Const(value),
+                (None, Ok(param)) => Operation::Param(param, Channel::Both),
+                (None, Err(_)) => {
+                    let variable = self.variable(name);
+                    if !self.variables.iter().any(|(v, _)| Rc::ptr_eq(v, &variable)) {
+                        self.variables.push((variable.clone(), expr.span));
+                    }
+                    Operation::Var(variable)
+                }
+            },
+            ExprKind::Band(..) | ExprKind::BarPhase(_) => Operation::Param(self.audio_param(expr)?, Channel::Both),
+            ExprKind::Channel(param_expr, channel) => {
+                let param = self.audio_param(param_expr);
+                let channel = self.channel(channel);
+                let param = param?;
+                if !param.is_per_channel() {
+                    // Only named parameters and bar_phase can reach here, because bands are measured per channel.
+                    let name = match &param_expr.kind {
+                        ExprKind::Name(name) => name.clone(),
+                        _ => "bar_phase".to_string()
+                    };
+                    self.error(ErrorKind::UnexpectedChannel(name), expr.span);
+                    return None;
+                }
+                Operation::Param(param, channel?)
+            },
+            ExprKind::Neg(a) => Operation::Neg(Box::new(self.expression(a)?)),
+            ExprKind::Binary(op, a, b) => {
+                let a = self.expression(a).map(Box::new);
+                let b_span = b.span;
+                let b = self.expression(b).map(Box::new);
+                let (a, b) = (a?, b?);
+                if matches!(op, BinaryOp::Div | BinaryOp::Mod) && b.constant_value() == Some(0.0) {
+                    self.error(ErrorKind::DivisionByZero, b_span);
+                    return None;
+                }
+                match op {
+                    BinaryOp::Add => Operation::Add(a, b),
+                    BinaryOp::Sub => Operation::Sub(a, b),
+                    BinaryOp::Mul => Operation::Mul(a, b),
+                    BinaryOp::Div => Operation::Div(a, b),
+                    BinaryOp::Mod => Operation::Mod(a, b),
+                    BinaryOp::Lt => Operation::Lt(a, b),
+                    BinaryOp::Le => Operation::Le(a, b),
+                    BinaryOp::Gt => Operation::Gt(a, b),
+                    BinaryOp::Ge => Operation::Ge(a, b),
+                    BinaryOp::Eq => Operation::Eq(a, b),
+                    BinaryOp::Ne => Operation::Ne(a, b),
+                    BinaryOp::And => Operation::And(a, b),
+                    BinaryOp::Or => Operation::Or(a, b),
+                }
+            },
+            ExprKind::If(cond, a, b) => {
+                let cond = self.expression(cond).map(Box::new);
+                let a = self.expression(a).map(Box::new);
+                let b = self.expression(b).map(Box::new);
+                Operation::If(cond?, a?, b?)
+            },
+            ExprKind::Call(name, args) => self.call(name, args)?,
+        })
+    }
+
+    /// Get the value of `i` (the index of the copy) or `n` (the number of copies) inside a repeated object.
+    fn copy_value(&self, name: &str) -> Option<f32> {
+        let (index, count) = self.copy?;
+        match name {
+            "i" => Some(index as f32),
+            "n" => Some(count as f32),
+            _ => None
+        }
+    }
+
+    /// Convert a call to a maths function or a filter.
+    fn call(&mut self, name: &Name, args: &[Expr]) -> Option<Operation> {
+        let args = args.iter().map(|arg| self.expression(arg)).collect::<Vec<_>>();
+        let function = Function::from_str(&name.text).ok();
+        let filter = FilterKind::from_str(&name.text).ok();
+        let arity = match (function, filter) {
+            (Some(function), _) => function.arity(),
+            (None, Some(kind)) => kind.arity(),
+            (None, None) => {
+                self.error(ErrorKind::UnrecognizedFunction(name.text.clone()), name.span);
+                return None;
+            }
+        };
+        if args.len() != arity {
+            self.error(ErrorKind::WrongArgumentCount(name.text.clone(), arity, args.len()), name.span);
+            return None;
+        }
+
+        let args = args.into_iter().collect::<Option<Vec<_>>>()?;
+        Some(match function {
+            Some(function) => Operation::Call(function, args),
+            None => Operation::Filter(Filter::new(filter?), args)
+        })
+    }
+
+    /// Resolve an audio parameter, which can't be a variable.
+    fn audio_param(&mut self, expr: &Expr) -> Option<AudioParam> {
+        match &expr.kind {
+            ExprKind::Name(name) => {
+                let param = AudioParam::from_str(name).ok();
+                if param.is_none() {
+                    self.error(ErrorKind::UnrecognizedAudioParam(name.clone()), expr.span);
+                }
+                param
+            },
+            ExprKind::Band(lo, hi) => {
+                // The band can be worked out from `i` and `n` in a repeated object, but can't change over time.
+                let lo = self.expression(lo).map(|lo| lo.constant_value());
+                let hi = self.expression(hi).map(|hi| hi.constant_value());
+                match (lo?, hi?) {
+                    (Some(lo), Some(hi)) if lo < hi => Some(AudioParam::Band(lo, hi)),
+                    (Some(lo), Some(hi)) => {
+                        self.error(ErrorKind::InvalidBand(lo, hi), expr.span);
+                        None
+                    },
+                    _ => {
+                        self.error(ErrorKind::NonConstantBand, expr.span);
+                        None
+                    }
+                }
+            },
+            ExprKind::BarPhase(None) => Some(AudioParam::BarPhase(4.0)),
+            ExprKind::BarPhase(Some(n)) => if *n > 0.0 {
+                Some(AudioParam::BarPhase(*n))
+            } else {
+                self.error(ErrorKind::InvalidBarLength(*n), expr.span);
+                None
+            },
+            _ => unreachable!("the grammar only allows audio parameters here")
+        }
+    }
+}
+
+/// Sort bindings so that each one comes after the variables that it reads.
+fn sort_bindings(bindings: Vec<(Binding, Span)>) -> Result<Vec<Binding>, ScriptError> {
+    let index_of = bindings.iter().enumerate()
+        .map(|(index, (binding, _))| (binding.variable().name(), index))
+        .collect::<HashMap<_, _>>();
+
+    // Variables without a binding have already been reported, so they are skipped here.
+    let dependencies = bindings.iter().map(|(binding, _)| {
+        let mut dependencies = Vec::new();
+        binding.operation().visit_variables(&mut |v| dependencies.extend(index_of.get(v.name())));
+        dependencies
+    }).collect::<Vec<_>>();
+
+    #[derive(Clone, Copy, PartialEq)]
+    enum State {
+        Unvisited,
+        Visiting,
+        Done
+    }
+
+    // Depth-first search, which adds each binding after all of its dependencies.
+    fn visit(index: usize, dependencies: &[Vec<usize>], states: &mut [State], stack: &mut Vec<usize>, order: &mut Vec<usize>) -> Result<(), Vec<usize>> {
+        match states[index] {
+            State::Done => return Ok(()),
+            State::Visiting => {
+                // Found a cycle: return the part of the stack that loops back to this binding.
+                let start = stack.iter().position(|i| *i == index).unwrap();
+                let mut cycle = stack[start..].to_vec();
+                cycle.push(index);
+                return Err(cycle);
+            },
+            State::Unvisited => {}
+        }
+        states[index] = State::Visiting;
+        stack.push(index);
+        for dependency in &dependencies[index] {
+            visit(*dependency, dependencies, states, stack, order)?;
+        }
+        stack.pop();
+        states[index] = State::Done;
+        order.push(index);
+        Ok(())
+    }
+
+    let mut states = vec![State::Unvisited; bindings.len()];
+    let mut order = Vec::with_capacity(bindings.len());
+    for index in 0..bindings.len() {
+        visit(index, &dependencies, &mut states, &mut Vec::new(), &mut order)
+            .map_err(|cycle| ScriptError::new(
+                ErrorKind::CyclicBinding(cycle.iter().map(|i| bindings[*i].0.variable().name().to_string()).collect()),
+                bindings[cycle[0]].1
+            ))?;
+    }
+
+    let mut bindings = bindings.into_iter().map(|(binding, _)| Some(binding)).collect::<Vec<_>>();
+    Ok(order.into_iter().map(|i| bindings[i].take().unwrap()).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BG: &str = "bg = { r = 0, g = 0, b = 0 }";
+    const QUAD: &str = "x = 0, y = 0, width = 1, height = 1, r = 1, g = 1, b = 1";
+
+    /// Make bindings from names and the names that they read, each at a span made from its index.
+    fn bindings(defs: &[(&str, &[&str])]) -> Vec<(Binding, Span)> {
+        let mut names = HashMap::new();
+        let mut variable = |name: &str| names.entry(name.to_string())
+            .or_insert_with(|| Rc::new(Variable::new(name.to_string())))
+            .clone();
+        defs.iter().enumerate().map(|(index, (name, reads))| {
+            let operation = reads.iter().fold(Operation::Const(1.0), |sum, read| {
+                Operation::Add(Box::new(sum), Box::new(Operation::Var(variable(read))))
+            });
+            (Binding::new(variable(name), operation), Span::new(index, index + 1))
+        }).collect()
+    }
+
+    fn sorted_names(defs: &[(&str, &[&str])]) -> Vec<String> {
+        sort_bindings(bindings(defs)).unwrap().iter()
+            .map(|binding| binding.variable().name().to_string())
+            .collect()
+    }
+
+    fn cycle(defs: &[(&str, &[&str])]) -> (Vec<String>, Span) {
+        match sort_bindings(bindings(defs)) {
+            Err(ScriptError { kind: ErrorKind::CyclicBinding(names), span }) => (names, span),
+            Err(e) => panic!("unexpected error {e:?}"),
+            Ok(_) => panic!("no cycle found"),
+        }
+    }
+
+    #[test]
+    fn bindings_come_after_their_dependencies() {
+        assert_eq!(sorted_names(&[("a", &["b"]), ("b", &["c"]), ("c", &[])]), ["c", "b", "a"]);
+        assert_eq!(sorted_names(&[("a", &[]), ("b", &[]), ("c", &["a"])]), ["a", "b", "c"]);
+        assert_eq!(sorted_names(&[("d", &["b", "c"]), ("b", &["a"]), ("c", &["a"]), ("a", &[])]), ["a", "b", "c", "d"]);
+    }
+
+    #[test]
+    fn cycles_are_reported_in_order() {
+        assert_eq!(cycle(&[("a", &["a"])]), (vec!["a".to_string(); 2], Span::new(0, 1)));
+        let (names, span) = cycle(&[("a", &["b"]), ("b", &["c"]), ("c", &["a"])]);
+        assert_eq!(names, ["a", "b", "c", "a"]);
+        assert_eq!(span, Span::new(0, 1));
+    }
+
+    #[test]
+    fn cycles_start_where_they_are_entered() {
+        // `a` leads into the cycle, but isn't part of it.
+        let (names, span) = cycle(&[("a", &["b"]), ("b", &["c"]), ("c", &["b"])]);
+        assert_eq!(names, ["b", "c", "b"]);
+        assert_eq!(span, Span::new(1, 2));
+    }
+
+    fn check(source: &str) -> Result<Program, Vec<ScriptError>> {
+        let script = match super::super::parse(source) {
+            Ok(script) => script,
+            Err(e) => panic!("{} at {:?}", e.kind, e.span),
+        };
+        validate(&script, Path::new(""))
+    }
+
+    /// Validate a script with the given bindings and objects, and return the messages of its errors.
+    fn errors(bindings: &str, objects: &str) -> Vec<String> {
+        let source = format!("{bindings}\ndisplay = scene {{ {BG} objects = [ {objects} ] }}");
+        match check(&source) {
+            Ok(_) => Vec::new(),
+            Err(errors) => errors.iter().map(|e| e.kind.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn valid_script() {
+        let source = format!("\
+            let glow = smooth(level, 0.1) * scale
+            let scale = 2
+            display = scene {{ {BG} objects = [
+                quad {{ {QUAD}, a = if glow > 1 then 1 else glow }},
+                group {{ x = 0.5, objects = [ circle {{ x = 0, y = 0, radius = bass.left, line_width = 0.1, r = 1, g = 1, b = 1 }} ] }},
+                repeat 3 {{ quad {{ {QUAD}, blend = additive }} }}
+            ] }}");
+        let program = check(&source).map_err(|errors| errors.iter().map(|e| e.kind.to_string()).collect::<Vec<_>>()).unwrap();
+        assert_eq!(program.objects.len(), 3);
+        let names = program.bindings.iter().map(|b| b.variable().name()).collect::<Vec<_>>();
+        assert_eq!(names, ["scale", "glow"]);
+    }
+
+    #[test]
+    fn unrecognized_names() {
+        assert_eq!(errors("", &format!("triangle {{ {QUAD} }}")), ["unrecognized object triangle"]);
+        assert_eq!(errors("", &format!("quad {{ {QUAD}, wobble = 1 }}")), ["unrecognized render parameter wobble"]);
+        assert_eq!(errors("", &format!("quad {{ {QUAD}, blend = glow }}")), ["unrecognized blend mode glow"]);
+        assert_eq!(errors("", &format!("quad {{ {QUAD}, a = wobble(1) }}")), ["unrecognized function wobble"]);
+        assert_eq!(errors("", &format!("quad {{ {QUAD}, a = bass.middle }}")), ["unrecognized channel middle"]);
+        assert_eq!(errors("", &format!("quad {{ {QUAD}, a = glow }}")), ["unrecognized audio parameter or variable glow"]);
+    }
+
+    #[test]
+    fn expression_errors() {
+        assert_eq!(errors("", &format!("quad {{ {QUAD}, a = min(1) }}")), ["function min takes 2 arguments, but 1 were given"]);
+        assert_eq!(errors("", &format!("quad {{ {QUAD}, a = smooth(bass, 1, 2) }}")), ["function smooth takes 2 arguments, but 3 were given"]);
+        assert_eq!(errors("", &format!("quad {{ {QUAD}, a = bass / (1 - 1) }}")), ["division by zero"]);
+        assert_eq!(errors("", &format!("quad {{ {QUAD}, a = band(200, 100) }}")), ["invalid frequency band 200..100 Hz"]);
+        assert_eq!(errors("", &format!("quad {{ {QUAD}, a = band(100, 100 + bass) }}")), ["frequency band must be constant: it can't use audio parameters or variables"]);
+        assert_eq!(errors("", &format!("quad {{ {QUAD}, a = band(100, smooth(200, 1)) }}")), ["frequency band must be constant: it can't use audio parameters or variables"]);
+        assert!(errors("", &format!("repeat 8 {{ quad {{ {QUAD}, a = band(i * 100, i * 100 + 100).left }} }}")).is_empty());
+        assert_eq!(errors("", &format!("repeat 8 {{ quad {{ {QUAD}, a = band(i * 100, 300) }} }}")), ["invalid frequency band 300..300 Hz"]);
+        assert_eq!(errors("", &format!("quad {{ {QUAD}, a = bar_phase(0) }}")), ["invalid number of beats in bar: 0"]);
+        assert_eq!(errors("", &format!("quad {{ {QUAD}, a = time.left }}")), ["time is the same for every channel, so it can't have a channel"]);
+    }
+
+    #[test]
+    fn every_missing_and_extra_parameter() {
+        assert_eq!(errors("", "quad { x = 0 }"), [
+            "missing required parameter y in quad (objects[0])",
+            "missing required parameter width in quad (objects[0])",
+            "missing required parameter height in quad (objects[0])",
+            "missing required parameter r in quad (objects[0])",
+            "missing required parameter g in quad (objects[0])",
+            "missing required parameter b in quad (objects[0])",
+        ]);
+        assert_eq!(errors("", &format!("quad {{ sides = 3, {QUAD}, radius = 1 }}")), [
+            "invalid parameter sides in quad (objects[0])",
+            "invalid parameter radius in quad (objects[0])",
+        ]);
+        assert_eq!(errors("", "repeat 3 { circle { x = 0, y = 0, radius = 1, r = 1, g = 1, b = 1, width = 1 } }"), [
+            "missing required parameter line_width in circle (objects[0])",
+            "invalid parameter width in circle (objects[0])",
+        ]);
+        assert_eq!(errors("", "group { width = 1, height = 1, objects = [ text { text = \"hi\" } ] }"), [
+            "invalid parameter width in group (objects[0])",
+            "invalid parameter height in group (objects[0])",
+            "missing required parameter x in text (objects[0].objects[0])",
+            "missing required parameter y in text (objects[0].objects[0])",
+            "missing required parameter size in text (objects[0].objects[0])",
+            "missing required parameter r in text (objects[0].objects[0])",
+            "missing required parameter g in text (objects[0].objects[0])",
+            "missing required parameter b in text (objects[0].objects[0])",
+        ]);
+    }
+
+    #[test]
+    fn failed_values_still_check_the_other_parameters() {
+        let missing = [
+            "missing required parameter y in quad (objects[0])",
+            "missing required parameter width in quad (objects[0])",
+            "missing required parameter height in quad (objects[0])",
+            "missing required parameter r in quad (objects[0])",
+            "missing required parameter g in quad (objects[0])",
+            "missing required parameter b in quad (objects[0])",
+        ];
+        for (value, error) in [
+            ("bogus", "unrecognized audio parameter or variable bogus"),
+            ("min(1)", "function min takes 2 arguments, but 1 were given"),
+            ("\"1\"", "parameter x must be a number, not a string"),
+            ("[(0, 0), (1, 1)]", "parameter x must be a number, not a list of points")
+        ] {
+            // Missing parameters are reported at the name of the object, which comes first.
+            let mut expected = missing.to_vec();
+            expected.push(error);
+            assert_eq!(errors("", &format!("quad {{ x = {value} }}")), expected, "x = {value}");
+        }
+        assert_eq!(errors("", "repeat 2 { quad { x = 1 / 0, sides = min(1), blend = glow, width = 1 } }"), [
+            "missing required parameter y in quad (objects[0])",
+            "missing required parameter height in quad (objects[0])",
+            "missing required parameter r in quad (objects[0])",
+            "missing required parameter g in quad (objects[0])",
+            "missing required parameter b in quad (objects[0])",
+            "division by zero",
+            "invalid parameter sides in quad (objects[0])",
+            "function min takes 2 arguments, but 1 were given",
+            "unrecognized blend mode glow",
+        ]);
+        assert_eq!(errors("", &format!("group {{ x = bogus, objects = [ quad {{ {QUAD} }} ] }}")), ["unrecognized audio parameter or variable bogus"]);
+        assert_eq!(errors("", &format!("quad {{ {QUAD}, join = \"round\", text = 1 }}")), [
+            "invalid parameter join in quad (objects[0])",
+            "join must be one of: miter, round, bevel",
+            "invalid parameter text in quad (objects[0])",
+            "parameter text must be a string",
+        ]);
+    }
+
+    #[test]
+    fn groups_have_no_blend_mode() {
+        assert_eq!(errors("", &format!("group {{ blend = additive, objects = [ quad {{ {QUAD}, blend = additive }} ] }}")), [
+            "invalid parameter blend in group (objects[0])",
+        ]);
+    }
+
+    #[test]
+    fn cycles_are_reported_with_other_errors() {
+        assert_eq!(errors("let a = b + c\nlet b = a", &format!("quad {{ {QUAD}, blend = glow }}")), [
+            "variables depend on each other: a -> b -> a",
+            "unrecognized audio parameter or variable c",
+            "unrecognized blend mode glow",
+        ]);
+    }
+
+    #[test]
+    fn binding_errors() {
+        assert_eq!(errors("let bass = 1", &format!("quad {{ {QUAD} }}")), ["cannot define variable bass: it is the name of an audio parameter"]);
+        assert_eq!(errors("let a = 1\nlet a = 2", &format!("quad {{ {QUAD}, r = a }}")), ["variable a is defined more than once"]);
+        assert_eq!(errors("let a = b\nlet b = a + 1", &format!("quad {{ {QUAD} }}")), ["variables depend on each other: a -> b -> a"]);
+    }
+
+    #[test]
+    fn repeats() {
+        assert_eq!(errors("", &format!("repeat 0 {{ quad {{ {QUAD} }} }}")), [format!("repeat count must be a constant whole number from 1 to {MAX_COPIES}")]);
+        assert_eq!(errors("", &format!("repeat bass {{ quad {{ {QUAD} }} }}")), [format!("repeat count must be a constant whole number from 1 to {MAX_COPIES}")]);
+        assert_eq!(errors("", &format!("repeat 2 {{ group {{ objects = [ quad {{ {QUAD} }} ] }} }}")), ["group can't be repeated"]);
+        // Errors in a repeated object are only reported for the first copy.
+        assert_eq!(errors("", &format!("repeat 3 {{ quad {{ {QUAD}, a = 1 / (i - 1) }} }}")), ["division by zero"]);
+        assert!(errors("", &format!("repeat 3 {{ quad {{ {QUAD}, a = i / n }} }}")).is_empty());
+    }
+
+    #[test]
+    fn strings_and_points() {
+        assert_eq!(errors("", &format!("quad {{ {QUAD}, a = \"1\" }}")), ["parameter a must be a number, not a string"]);
+        assert_eq!(errors("", "text { x = 0, y = 0, size = 1, r = 1, g = 1, b = 1, text = \"{bass:x}\" }"), ["invalid format :x: expected a number of decimal places from 0 to 9, such as :.2"]);
+        assert_eq!(errors("", "text { x = 0, y = 0, size = 1, r = 1, g = 1, b = 1, text = \"{bass:.10}\" }"), ["invalid format :.10: expected a number of decimal places from 0 to 9, such as :.2"]);
+        assert!(errors("", "text { x = 0, y = 0, size = 1, r = 1, g = 1, b = 1, text = \"{bass:.9}\" }").is_empty());
+        assert_eq!(errors("", "text { x = 0, y = 0, size = 1, r = 1, g = 1, b = 1, text = \"{bass\" }"), ["unmatched brace in string: use {{ or }} for a literal brace"]);
+        assert_eq!(errors("", &format!("quad {{ {QUAD}, a = [(0, 0), (1, 1)] }}")), ["parameter a must be a number, not a list of points"]);
+        assert_eq!(errors("", "polyline { points = [(0, 0)], line_width = 0.1, r = 1, g = 1, b = 1 }"), ["a line needs at least 2 points"]);
+    }
+
+    #[test]
+    fn long_text_is_reported() {
+        let text = |s: &str| errors("", &format!("text {{ x = 0, y = 0, size = 1, r = 1, g = 1, b = 1, text = \"{s}\" }}"));
+        assert!(text(&"a".repeat(MAX_GLYPHS)).is_empty());
+        // Spaces and values aren't counted.
+        assert!(text(&format!("{} {{bpm}} {{title}}", "a b".repeat(MAX_GLYPHS / 2))).is_empty());
+        assert_eq!(text(&"a".repeat(MAX_GLYPHS + 1)), ["text has 257 characters, but at most 256 can be drawn (not counting spaces)"]);
+        assert_eq!(text(&format!("{}{{bpm}}{{{{", "a".repeat(MAX_GLYPHS))), ["text has 257 characters, but at most 256 can be drawn (not counting spaces)"]);
+    }
+
+    #[test]
+    fn templates_are_filled_in() {
+        let packet = crate::audio::tests::packet_at_120_bpm("tracks/Click Track.wav");
+        let eval = |s: &str| {
+            let mut validator = Validator::new(Path::new(""));
+            let mut template = validator.text(&ast::StringLiteral { text: s.to_string(), span: Span::new(0, s.len() + 2) })
+                .unwrap_or_else(|| panic!("{}", validator.errors[0].kind));
+            template.eval(&packet).to_string()
+        };
+        assert_eq!(eval("{bpm:.0} BPM"), "120 BPM");
+        assert_eq!(eval("{{bpm}} is {{{bpm:.0}}}"), "{bpm} is {120}");
+        assert_eq!(eval("}}{{"), "}{");
+        assert_eq!(eval("{title}: {artist}"), "Click Track: ");
+        assert_eq!(eval("{ title }{album}"), "Click Track");
+        assert_eq!(eval("{time} {time:.2} {time / 4:.1} {time / 3:.0} {beat_count > 0}"), "10 10.00 2.5 3 1");
+        assert_eq!(eval(""), "");
+    }
+
+    #[test]
+    fn single_channels_are_noted() {
+        let per_channel = |objects: &str| match check(&format!("display = scene {{ {BG} objects = [ {objects} ] }}")) {
+            Ok(program) => program.per_channel,
+            Err(errors) => panic!("{:?}", errors.iter().map(|e| e.kind.to_string()).collect::<Vec<_>>()),
+        };
+        assert!(!per_channel(&format!("quad {{ {QUAD}, a = level + balance }}")));
+        assert!(!per_channel(&format!("quad {{ {QUAD}, a = level.both }}")));
+        assert!(per_channel(&format!("quad {{ {QUAD}, a = band(60, 120).right }}")));
+        assert!(per_channel(&format!("group {{ objects = [ quad {{ {QUAD}, a = level.left }} ] }}")));
+        assert!(per_channel("waveform { x = 0, y = 0, width = 1, height = 1, line_width = 0.01, r = 1, g = 1, b = 1, channel = left }"));
+        assert!(!per_channel("waveform { x = 0, y = 0, width = 1, height = 1, line_width = 0.01, r = 1, g = 1, b = 1, channel = both }"));
+    }
+
+    #[test]
+    fn errors_are_in_source_order() {
+        let objects = format!("quad {{ {QUAD}, a = nope }}, quad {{ {QUAD}, blend = glow }}");
+        let errors = errors("let a = min(1)", &objects);
+        assert_eq!(errors, [
+            "function min takes 2 arguments, but 1 were given",
+            "unrecognized audio parameter or variable nope",
+            "unrecognized blend mode glow",
+        ]);
+    }
+}
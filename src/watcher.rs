@@ -0,0 +1,50 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::mpsc
+};
+
+use notify::Watcher;
+
+/// Watches a file for changes.
+pub struct FileWatcher {
+    // Stops watching when dropped.
+    _watcher: notify::RecommendedWatcher,
+    changes: mpsc::Receiver<()>
+}
+
+impl FileWatcher {
+    pub fn new(path: &Path) -> notify::Result<Self> {
+        let path = path.canonicalize()?;
+        let dir = path.parent().map(Path::to_path_buf).unwrap_or_else(|| PathBuf::from("."));
+        let file_name = path.file_name().map(|n| n.to_os_string());
+
+        let (sender, changes) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+            let Ok(event) = event else {
+                return;
+            };
+            if event.kind.is_access() {
+                return;
+            }
+            if event.paths.iter().any(|p| p.file_name().map(|n| n.to_os_string()) == file_name) {
+                let _ = sender.send(());
+            }
+        })?;
+        // Watch the whole directory, as many editors save by replacing the file.
+        watcher.watch(&dir, notify::RecursiveMode::NonRecursive)?;
+
+        Ok(Self {
+            _watcher: watcher,
+            changes
+        })
+    }
+
+    /// Check if the file has changed since this was last called.
+    pub fn changed(&self) -> bool {
+        let mut changed = false;
+        while self.changes.try_recv().is_ok() {
+            changed = true;
+        }
+        changed
+    }
+}